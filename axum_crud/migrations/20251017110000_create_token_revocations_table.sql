@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Revoked access tokens: either a single token (jti) or every token a credential was issued before a cutoff.
+-- Rows are only needed until the tokens they cover would have expired anyway, after which they are pruned.
+CREATE TABLE IF NOT EXISTS token_revocations (
+    id SERIAL PRIMARY KEY,
+    credential_id INTEGER NOT NULL REFERENCES credentials(id) ON DELETE CASCADE,
+    jti UUID UNIQUE,
+    issued_before TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CHECK (jti IS NOT NULL OR issued_before IS NOT NULL)
+);
+
+CREATE INDEX idx_token_revocations_credential_id ON token_revocations(credential_id);
+CREATE INDEX idx_token_revocations_expires_at ON token_revocations(expires_at);
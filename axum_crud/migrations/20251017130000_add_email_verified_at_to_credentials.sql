@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE credentials ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,10 @@
+-- Add migration script here
+-- One TOTP secret per credential, AES-256-GCM encrypted. confirmed_at stays NULL until the
+-- user proves they enrolled it; last_used_step blocks replaying a code inside its window.
+CREATE TABLE IF NOT EXISTS totp_secrets (
+    credential_id INTEGER PRIMARY KEY REFERENCES credentials(id) ON DELETE CASCADE,
+    secret_encrypted BYTEA NOT NULL,
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    last_used_step BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
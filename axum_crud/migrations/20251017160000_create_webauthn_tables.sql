@@ -0,0 +1,30 @@
+-- Add migration script here
+-- Stable, opaque WebAuthn user handle per account (never the numeric id or the email)
+ALTER TABLE credentials ADD COLUMN IF NOT EXISTS webauthn_user_id UUID NOT NULL DEFAULT gen_random_uuid();
+CREATE UNIQUE INDEX IF NOT EXISTS idx_credentials_webauthn_user_id ON credentials(webauthn_user_id);
+
+-- Registered passkeys/security keys; `passkey` is the serialized webauthn-rs Passkey
+CREATE TABLE IF NOT EXISTS webauthn_credentials (
+    id SERIAL PRIMARY KEY,
+    credential_id INTEGER NOT NULL REFERENCES credentials(id) ON DELETE CASCADE,
+    passkey_id BYTEA NOT NULL UNIQUE,
+    passkey JSONB NOT NULL,
+    name VARCHAR(255),
+    sign_count BIGINT NOT NULL DEFAULT 0,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_webauthn_credentials_credential_id ON webauthn_credentials(credential_id);
+
+-- In-flight registration/authentication ceremonies; each row is consumed by the matching finish call
+CREATE TABLE IF NOT EXISTS webauthn_challenges (
+    id UUID PRIMARY KEY,
+    credential_id INTEGER NOT NULL REFERENCES credentials(id) ON DELETE CASCADE,
+    kind VARCHAR(32) NOT NULL,
+    state JSONB NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_webauthn_challenges_expires_at ON webauthn_challenges(expires_at);
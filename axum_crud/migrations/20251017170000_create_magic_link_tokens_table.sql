@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Every magic link request is recorded (even for unknown addresses, with no token) so the per-address
+-- rate limit behaves identically whether or not the account exists
+CREATE TABLE IF NOT EXISTS magic_link_tokens (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    credential_id INTEGER REFERENCES credentials(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) UNIQUE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_magic_link_tokens_email_created_at ON magic_link_tokens(email, created_at);
+CREATE INDEX idx_magic_link_tokens_expires_at ON magic_link_tokens(expires_at);
@@ -0,0 +1,19 @@
+-- Add migration script here
+-- Failed password logins keyed by normalized email rather than credential id, so unknown addresses are
+-- tracked and locked exactly like real ones and the lockout can't be used to probe for accounts
+CREATE TABLE IF NOT EXISTS login_attempts (
+    email VARCHAR(255) PRIMARY KEY,
+    failed_count INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_login_attempts_locked_until ON login_attempts(locked_until);
+
+INSERT INTO permissions (name, description) VALUES ('lockouts:manage', 'View and clear login lockouts')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT roles.id, permissions.id FROM roles, permissions
+WHERE roles.name = 'admin' AND permissions.name = 'lockouts:manage'
+ON CONFLICT DO NOTHING;
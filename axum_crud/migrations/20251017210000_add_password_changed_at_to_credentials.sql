@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Set whenever the password is changed or reset; NULL means unchanged since registration
+ALTER TABLE credentials ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMP WITH TIME ZONE;
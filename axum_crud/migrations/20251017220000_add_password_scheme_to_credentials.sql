@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Hashes imported from other systems keep their original format and are tagged with the scheme that made them
+-- (pbkdf2_sha256, scrypt, django, salted_sha1); NULL means a native Argon2id or bcrypt hash.
+-- The tag is cleared when the hash is upgraded on the user's first login.
+ALTER TABLE credentials ADD COLUMN IF NOT EXISTS password_scheme VARCHAR(32);
+
+INSERT INTO permissions (name, description) VALUES ('credentials:import', 'Bulk import accounts from other systems')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT roles.id, permissions.id FROM roles, permissions
+WHERE roles.name = 'admin' AND permissions.name = 'credentials:import'
+ON CONFLICT DO NOTHING;
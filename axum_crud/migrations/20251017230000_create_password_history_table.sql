@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Every password an account has had, newest first by created_at; only the most recent
+-- PASSWORD_HISTORY_DEPTH rows per account are kept. Existing hashes seed the history.
+CREATE TABLE IF NOT EXISTS password_history (
+    id SERIAL PRIMARY KEY,
+    credential_id INTEGER NOT NULL REFERENCES credentials(id) ON DELETE CASCADE,
+    password_hash VARCHAR(255) NOT NULL,
+    password_scheme VARCHAR(32),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_password_history_credential_id_created_at ON password_history(credential_id, created_at DESC);
+
+INSERT INTO password_history (credential_id, password_hash, password_scheme, created_at)
+SELECT id, password, password_scheme, COALESCE(password_changed_at, created_at, NOW()) FROM credentials;
@@ -0,0 +1,8 @@
+-- Add migration script here
+INSERT INTO permissions (name, description) VALUES ('metrics:read', 'Read operational metrics')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT roles.id, permissions.id FROM roles, permissions
+WHERE roles.name = 'admin' AND permissions.name = 'metrics:read'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Keyset pagination orders by (created_at, id), so the column can't be NULL
+UPDATE credentials SET created_at = NOW() WHERE created_at IS NULL;
+ALTER TABLE credentials ALTER COLUMN created_at SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_credentials_created_at_id ON credentials(created_at, id);
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- A cutoff revokes every token issued up to and including its second (`iat` has second precision), so the
+-- fresh token handed to the caller that triggered it, e.g. on a password change, is exempted by its jti.
+ALTER TABLE token_revocations ADD COLUMN IF NOT EXISTS exempt_jti UUID;
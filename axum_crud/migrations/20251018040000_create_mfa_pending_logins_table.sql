@@ -0,0 +1,13 @@
+-- Add migration script here
+-- One row per mfa_pending token, keyed by the token's id, so a token can be spent once
+-- and is burned after too many wrong codes
+CREATE TABLE IF NOT EXISTS mfa_pending_logins (
+    id UUID PRIMARY KEY,
+    credential_id INTEGER NOT NULL REFERENCES credentials(id) ON DELETE CASCADE,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    consumed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_mfa_pending_logins_expires_at ON mfa_pending_logins(expires_at);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Lets the pruning job find expired refresh tokens without scanning the table
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_expires_at ON refresh_tokens(expires_at);
@@ -0,0 +1,14 @@
+use serde::{Deserialize};
+
+// `sign_out_other_sessions` (default true) revokes every other session and token plus all API keys; bearer callers get a fresh pair
+#[derive(Deserialize,Debug)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+    #[serde(default = "default_sign_out_other_sessions")]
+    pub sign_out_other_sessions: bool,
+}
+
+fn default_sign_out_other_sessions() -> bool {
+    true
+}
@@ -0,0 +1,21 @@
+use axum::{
+    extract::{State},
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::account::dto::ChangePasswordRequest;
+use crate::account::services::change_password_service;
+use crate::auth::extractor::Authenticated;
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+
+#[axum::debug_handler]
+pub async fn change_password_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Json(body): Json<ChangePasswordRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = change_password_service(&auth, body, &state.db, &state.jwt, &state.auth_config, &state.passwords, &state.password_policy).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
@@ -0,0 +1,5 @@
+pub mod dto;
+pub mod model;
+pub mod services;
+pub mod handler;
+pub mod routes;
@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize};
+
+use crate::auth::model::TokenPairResponse;
+
+
+#[derive(Serialize)]
+pub struct PasswordChangedResponse {
+    pub password_changed_at: DateTime<Utc>,
+    // Replaces the caller's revoked bearer tokens; absent for sessions or when nothing was revoked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<TokenPairResponse>,
+}
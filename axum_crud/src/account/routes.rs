@@ -0,0 +1,11 @@
+use axum::{
+    routing::post,
+    Router
+};
+use crate::account::handler::change_password_handler;
+use crate::grouped_routes::main_route::AppState;
+
+pub fn account_routes() -> Router<AppState> {
+    Router::new()
+      .route("/password", post(change_password_handler))
+}
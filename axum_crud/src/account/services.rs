@@ -0,0 +1,203 @@
+use sqlx::PgPool;
+
+use crate::account::dto::ChangePasswordRequest;
+use crate::account::model::PasswordChangedResponse;
+use crate::auth::config::AuthConfig;
+use crate::auth::extractor::Authenticated;
+use crate::auth::jwt::JwtKeys;
+use crate::auth::services::{complete_token_pair_service, password_changed_concurrently, revoke_other_credential_access};
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::repository::{get_stored_credentials_by_id_repository, update_password_repository};
+use crate::passwords::pool::HashingPool;
+use crate::passwords::policy::PasswordPolicy;
+use crate::passwords::services::{ensure_password_not_reused_service, record_password_history_service};
+use crate::lockout::services::{clear_failed_logins_service, ensure_not_locked_service, record_failed_login_service};
+
+
+// A wrong current password counts as a failed login, so a hijacked session can't be used to guess it
+pub async fn change_password_service(
+    auth: &Authenticated,
+    input: ChangePasswordRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    config: &AuthConfig,
+    passwords: &HashingPool,
+    password_policy: &PasswordPolicy,
+) -> AppResult<PasswordChangedResponse> {
+    let credentials = get_stored_credentials_by_id_repository(auth.credential_id(), pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("Credentials"))?;
+
+    ensure_not_locked_service(&credentials.email, pool).await?;
+    if !passwords.verify(&input.current_password, &credentials.password_hash, credentials.password_scheme.as_deref()).await? {
+        record_failed_login_service(&credentials.email, pool, config).await?;
+        return Err(AppError::authentication("Current password is incorrect"));
+    }
+    clear_failed_logins_service(&credentials.email, pool).await?;
+
+    password_policy.check(&input.new_password, Some(&credentials.email))?;
+
+    // Bearer callers get a fresh pair; its access token is minted first so the cutoff below can exempt it
+    let access_token = match auth {
+        Authenticated::Bearer(_) if input.sign_out_other_sessions => {
+            Some(jwt.issue_access_token(credentials.id, &credentials.email)?)
+        }
+        _ => None,
+    };
+
+    ensure_password_not_reused_service(pool, credentials.id, &input.new_password, config.password_history_depth, passwords)
+        .await?;
+    let hashed_password = passwords.hash(&input.new_password).await?;
+
+    // Everything slow is done; the update only goes through if the password is still the one verified above
+    let mut tx = pool.begin().await?;
+    let password_changed_at = update_password_repository(&mut *tx, credentials.id, &credentials.password_hash, &hashed_password)
+        .await?
+        .ok_or_else(password_changed_concurrently)?;
+    record_password_history_service(&mut tx, credentials.id, &hashed_password, config.password_history_depth).await?;
+    if input.sign_out_other_sessions {
+        let keep_session_hash = match auth {
+            Authenticated::Session(session) => Some(session.session_hash.as_str()),
+            Authenticated::Bearer(_) => None,
+        };
+        let keep_jti = access_token.as_ref().map(|token| token.jti);
+        revoke_other_credential_access(&mut tx, credentials.id, keep_session_hash, keep_jti, jwt).await?;
+    }
+    tx.commit().await?;
+
+    let tokens = match access_token {
+        Some(access_token) => Some(complete_token_pair_service(access_token, credentials.id, pool, jwt).await?),
+        None => None,
+    };
+
+    Ok(PasswordChangedResponse {
+        password_changed_at,
+        tokens,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{header, Method, Request, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::grouped_routes::main_route::AppState;
+    use crate::test_support::{json_request, send, send_json, test_state, wait_for_mail, RecordingMailer, TestResponse};
+
+    const EMAIL: &str = "account@example.com";
+    const PASSWORD: &str = "Correct-Horse-Battery-9";
+    const NEW_PASSWORD: &str = "Fresh-Staple-Lantern-4";
+
+    struct Tokens {
+        access: String,
+        refresh: String,
+    }
+
+    async fn registered_state(pool: PgPool) -> AppState {
+        let mailer = Arc::new(RecordingMailer::default());
+        let state = test_state(pool, mailer.clone(), |config| config.allow_unverified_login = true);
+        send_json(&state, Method::POST, "/credentials", json!({ "email": EMAIL, "password": PASSWORD })).await;
+        wait_for_mail(&mailer, EMAIL).await;
+        state
+    }
+
+    async fn bearer_login(state: &AppState) -> Tokens {
+        let login = send_json(state, Method::POST, "/crud/login", json!({ "email": EMAIL, "password": PASSWORD })).await.json();
+        Tokens {
+            access: login["access_token"].as_str().unwrap().to_string(),
+            refresh: login["refresh_token"].as_str().unwrap().to_string(),
+        }
+    }
+
+    async fn session_login(state: &AppState) -> String {
+        send_json(state, Method::POST, "/auth/session/login", json!({ "email": EMAIL, "password": PASSWORD }))
+            .await
+            .cookie()
+    }
+
+    fn with(mut request: Request<Body>, header_name: header::HeaderName, value: String) -> Request<Body> {
+        request.headers_mut().insert(header_name, value.parse().unwrap());
+        request
+    }
+
+    async fn change_password(state: &AppState, header_name: header::HeaderName, value: String, sign_out: bool) -> TestResponse {
+        let body = json!({ "current_password": PASSWORD, "new_password": NEW_PASSWORD, "sign_out_other_sessions": sign_out });
+        send(state, with(json_request(Method::POST, "/account/password", body), header_name, value)).await
+    }
+
+    async fn access_status(state: &AppState, access_token: &str) -> StatusCode {
+        let request = Request::builder().uri("/crud/me").body(Body::empty()).unwrap();
+        send(state, with(request, header::AUTHORIZATION, format!("Bearer {access_token}"))).await.status
+    }
+
+    async fn session_status(state: &AppState, cookie: &str) -> StatusCode {
+        let request = Request::builder().uri("/auth/session").body(Body::empty()).unwrap();
+        send(state, with(request, header::COOKIE, cookie.to_string())).await.status
+    }
+
+    async fn refresh_status(state: &AppState, refresh_token: &str) -> StatusCode {
+        send_json(state, Method::POST, "/auth/refresh", json!({ "refresh_token": refresh_token })).await.status
+    }
+
+    #[sqlx::test]
+    async fn a_bearer_change_revokes_everything_else_and_keeps_the_fresh_pair(pool: PgPool) {
+        let state = registered_state(pool).await;
+        let caller = bearer_login(&state).await;
+        let other = bearer_login(&state).await;
+        let cookie = session_login(&state).await;
+
+        let changed = change_password(&state, header::AUTHORIZATION, format!("Bearer {}", caller.access), true).await;
+        assert_eq!(changed.status, StatusCode::OK);
+        let fresh = &changed.json()["tokens"];
+
+        for access in [&caller.access, &other.access] {
+            assert_eq!(access_status(&state, access).await, StatusCode::UNAUTHORIZED);
+        }
+        for refresh in [&caller.refresh, &other.refresh] {
+            assert_eq!(refresh_status(&state, refresh).await, StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(session_status(&state, &cookie).await, StatusCode::UNAUTHORIZED);
+
+        // Minted before the cutoff, very likely in the same second, and still valid through exempt_jti
+        assert_eq!(access_status(&state, fresh["access_token"].as_str().unwrap()).await, StatusCode::OK);
+        assert_eq!(refresh_status(&state, fresh["refresh_token"].as_str().unwrap()).await, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn a_session_change_keeps_only_the_callers_session(pool: PgPool) {
+        let state = registered_state(pool).await;
+        let caller = session_login(&state).await;
+        let other = session_login(&state).await;
+        let bearer = bearer_login(&state).await;
+
+        let changed = change_password(&state, header::COOKIE, caller.clone(), true).await;
+        assert_eq!(changed.status, StatusCode::OK);
+        assert!(changed.json().get("tokens").is_none());
+
+        assert_eq!(session_status(&state, &caller).await, StatusCode::OK);
+        assert_eq!(session_status(&state, &other).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(access_status(&state, &bearer.access).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(refresh_status(&state, &bearer.refresh).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn opting_out_leaves_other_sessions_signed_in(pool: PgPool) {
+        let state = registered_state(pool).await;
+        let caller = bearer_login(&state).await;
+        let other = bearer_login(&state).await;
+        let cookie = session_login(&state).await;
+
+        let changed = change_password(&state, header::AUTHORIZATION, format!("Bearer {}", caller.access), false).await;
+        assert_eq!(changed.status, StatusCode::OK);
+        assert!(changed.json().get("tokens").is_none());
+
+        assert_eq!(access_status(&state, &caller.access).await, StatusCode::OK);
+        assert_eq!(access_status(&state, &other.access).await, StatusCode::OK);
+        assert_eq!(refresh_status(&state, &other.refresh).await, StatusCode::OK);
+        assert_eq!(session_status(&state, &cookie).await, StatusCode::OK);
+    }
+}
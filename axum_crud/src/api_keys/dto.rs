@@ -0,0 +1,11 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize};
+
+#[derive(Deserialize,Debug)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Omit for a key that never expires
+    pub expires_at: Option<DateTime<Utc>>,
+}
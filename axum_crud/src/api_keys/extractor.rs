@@ -0,0 +1,56 @@
+use axum::{
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+
+use crate::api_keys::keys::looks_like_api_key;
+use crate::api_keys::repository::use_api_key_repository;
+use crate::auth::extractor::bearer_token;
+use crate::auth::tokens::hash_token;
+use crate::policy::engine::action_matches;
+use crate::crud::error_traits::AppError;
+use crate::grouped_routes::main_route::AppState;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// Add `key: ApiKey` to a handler's arguments to require an API key, sent as `X-Api-Key: <key>`
+// or `Authorization: Bearer <key>`. Every accepted request updates the key's last_used_at.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub credential_id: i32,
+    pub email: String,
+    pub scopes: Vec<String>,
+}
+
+impl ApiKey {
+    // `*` grants everything, `resource:*` every action on one resource
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| action_matches(granted, scope))
+    }
+}
+
+// Whether the request carries an API key rather than an access token or session
+pub fn has_api_key(parts: &Parts) -> bool {
+    parts.headers.contains_key(API_KEY_HEADER) || bearer_token(parts).is_some_and(looks_like_api_key)
+}
+
+impl FromRequestParts<AppState> for ApiKey {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let key = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .or_else(|| bearer_token(parts).filter(|token| looks_like_api_key(token)))
+            .ok_or_else(|| AppError::authentication("Missing API key"))?;
+
+        use_api_key_repository(&state.db, &hash_token(key))
+            .await?
+            .ok_or_else(|| AppError::authentication("API key is invalid, expired or revoked"))
+    }
+}
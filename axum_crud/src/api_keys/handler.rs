@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::api_keys::dto::CreateApiKeyRequest;
+use crate::api_keys::extractor::ApiKey;
+use crate::api_keys::services::{
+    create_api_key_service,list_api_keys_service,revoke_api_key_service,current_api_key_service,
+};
+use crate::auth::extractor::Authenticated;
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+
+// Keys are managed by a logged-in user, never by another API key
+#[axum::debug_handler]
+pub async fn create_api_key_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Json(body): Json<CreateApiKeyRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = create_api_key_service(&auth, body, &state.db).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn list_api_keys_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    let response = list_api_keys_service(&auth, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn revoke_api_key_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    revoke_api_key_service(&auth, id, &state.db).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Lets a job check which key and scopes it is running with
+#[axum::debug_handler(state = AppState)]
+pub async fn current_api_key_handler(key: ApiKey) -> AppResult<impl IntoResponse> {
+    Ok((StatusCode::OK, Json(current_api_key_service(key))))
+}
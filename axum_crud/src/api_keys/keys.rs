@@ -0,0 +1,28 @@
+use rand::{rngs::OsRng, RngCore};
+
+use crate::auth::tokens::generate_token;
+
+const KEY_PREFIX: &str = "ak";
+
+// Keys look like `ak_<public id>_<secret>`; the `ak_<public id>` part is stored in clear so it can be shown in listings.
+// Returns (prefix, full key)
+pub fn generate_api_key() -> (String, String) {
+    let mut public_id = [0u8; 6];
+    OsRng.fill_bytes(&mut public_id);
+
+    let prefix = format!("{KEY_PREFIX}_{}", hex::encode(public_id));
+    let key = format!("{prefix}_{}", generate_token());
+    (prefix, key)
+}
+
+// Lets a bearer header carry either an access token or an API key
+pub fn looks_like_api_key(value: &str) -> bool {
+    value.starts_with(&format!("{KEY_PREFIX}_"))
+}
+
+// Scopes are free-form `resource:action` strings, e.g. `credentials:read`
+pub fn is_valid_scope(scope: &str) -> bool {
+    !scope.is_empty()
+        && scope.len() <= 64
+        && scope.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | ':' | '*'))
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize};
+
+
+// The only response that ever contains the full key
+#[derive(Serialize)]
+pub struct CreatedApiKeyResponse {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub key: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ApiKeyResponse {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct CurrentApiKeyResponse {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub credential_id: i32,
+    pub email: String,
+    pub scopes: Vec<String>,
+}
@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+use crate::api_keys::extractor::ApiKey;
+use crate::api_keys::model::ApiKeyResponse;
+use crate::crud::error_traits::AppResult;
+
+
+pub async fn insert_api_key_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    name: &str,
+    prefix: &str,
+    key_hash: &str,
+    scopes: &[String],
+    expires_at: Option<DateTime<Utc>>,
+) -> AppResult<(i32, DateTime<Utc>)> {
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO api_keys (credential_id, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, created_at
+        "#,
+        credential_id,
+        name,
+        prefix,
+        key_hash,
+        scopes,
+        expires_at
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok((record.id, record.created_at))
+}
+
+// Revoked keys are left out; expired ones stay listed so their owner can see why a job stopped working
+pub async fn list_api_keys_repository(
+    pool: &PgPool,
+    credential_id: i32,
+) -> AppResult<Vec<ApiKeyResponse>> {
+    let records = sqlx::query!(
+        r#"
+        SELECT id, name, prefix, scopes, expires_at, last_used_at, created_at
+        FROM api_keys
+        WHERE credential_id = $1 AND revoked_at IS NULL
+        ORDER BY created_at
+        "#,
+        credential_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| ApiKeyResponse {
+            id: r.id,
+            name: r.name,
+            prefix: r.prefix,
+            scopes: r.scopes,
+            expires_at: r.expires_at,
+            last_used_at: r.last_used_at,
+            created_at: r.created_at,
+        })
+        .collect())
+}
+
+pub async fn revoke_api_key_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    id: i32,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND credential_id = $2 AND revoked_at IS NULL",
+        id,
+        credential_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Keys belong to the account itself (there are no separate service-account principals), so signing the
+// account out everywhere has to take them down with its sessions and tokens
+pub async fn revoke_api_keys_for_credential_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<u64> {
+    let result = sqlx::query!(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE credential_id = $1 AND revoked_at IS NULL",
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Looks the key up and stamps last_used_at in one round trip; None for unknown, revoked or expired keys
+pub async fn use_api_key_repository(
+    pool: &PgPool,
+    key_hash: &str,
+) -> AppResult<Option<ApiKey>> {
+    let record = sqlx::query!(
+        r#"
+        UPDATE api_keys
+        SET last_used_at = NOW()
+        FROM credentials
+        WHERE api_keys.key_hash = $1
+          AND api_keys.revoked_at IS NULL
+          AND (api_keys.expires_at IS NULL OR api_keys.expires_at > NOW())
+          AND credentials.id = api_keys.credential_id
+        RETURNING api_keys.id, api_keys.name, api_keys.prefix, api_keys.credential_id, credentials.email, api_keys.scopes
+        "#,
+        key_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| ApiKey {
+        id: r.id,
+        name: r.name,
+        prefix: r.prefix,
+        credential_id: r.credential_id,
+        email: r.email,
+        scopes: r.scopes,
+    }))
+}
+
@@ -0,0 +1,15 @@
+use axum::{
+    routing::{get, delete},
+    Router
+};
+use crate::api_keys::handler::{
+    create_api_key_handler,list_api_keys_handler,revoke_api_key_handler,current_api_key_handler,
+};
+use crate::grouped_routes::main_route::AppState;
+
+pub fn api_keys_routes() -> Router<AppState> {
+    Router::new()
+      .route("/", get(list_api_keys_handler).post(create_api_key_handler))
+      .route("/current", get(current_api_key_handler))
+      .route("/{id}", delete(revoke_api_key_handler))
+}
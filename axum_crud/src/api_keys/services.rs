@@ -0,0 +1,85 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::api_keys::dto::CreateApiKeyRequest;
+use crate::api_keys::extractor::ApiKey;
+use crate::api_keys::keys::{generate_api_key, is_valid_scope};
+use crate::api_keys::model::{ApiKeyResponse, CreatedApiKeyResponse, CurrentApiKeyResponse};
+use crate::api_keys::repository::{insert_api_key_repository, list_api_keys_repository, revoke_api_key_repository};
+use crate::auth::extractor::Authenticated;
+use crate::auth::tokens::hash_token;
+use crate::crud::error_traits::{AppError, AppResult};
+
+
+// The key inherits the creating account's identity; scopes narrow what it may do
+pub async fn create_api_key_service(
+    auth: &Authenticated,
+    input: CreateApiKeyRequest,
+    pool: &PgPool,
+) -> AppResult<CreatedApiKeyResponse> {
+    let name = input.name.trim().to_string();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::validation("Name must be between 1 and 100 characters"));
+    }
+    if let Some(scope) = input.scopes.iter().find(|scope| !is_valid_scope(scope)) {
+        return Err(AppError::validation(format!("Invalid scope: {scope}")));
+    }
+    if input.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(AppError::validation("expires_at must be in the future"));
+    }
+
+    let mut scopes = input.scopes;
+    scopes.sort();
+    scopes.dedup();
+
+    let (prefix, key) = generate_api_key();
+    let (id, created_at) = insert_api_key_repository(
+        pool,
+        auth.credential_id(),
+        &name,
+        &prefix,
+        &hash_token(&key),
+        &scopes,
+        input.expires_at,
+    )
+    .await?;
+
+    Ok(CreatedApiKeyResponse {
+        id,
+        name,
+        prefix,
+        key,
+        scopes,
+        expires_at: input.expires_at,
+        created_at,
+    })
+}
+
+pub async fn list_api_keys_service(
+    auth: &Authenticated,
+    pool: &PgPool,
+) -> AppResult<Vec<ApiKeyResponse>> {
+    list_api_keys_repository(pool, auth.credential_id()).await
+}
+
+pub async fn revoke_api_key_service(
+    auth: &Authenticated,
+    id: i32,
+    pool: &PgPool,
+) -> AppResult<()> {
+    if !revoke_api_key_repository(pool, auth.credential_id(), id).await? {
+        return Err(AppError::not_found("API key"));
+    }
+    Ok(())
+}
+
+pub fn current_api_key_service(key: ApiKey) -> CurrentApiKeyResponse {
+    CurrentApiKeyResponse {
+        id: key.id,
+        name: key.name,
+        prefix: key.prefix,
+        credential_id: key.credential_id,
+        email: key.email,
+        scopes: key.scopes,
+    }
+}
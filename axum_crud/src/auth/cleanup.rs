@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::auth::repository::prune_expired_auth_records_repository;
+use crate::config::env::parse_or;
+
+const DEFAULT_PRUNE_INTERVAL_SECONDS: u64 = 60 * 60;
+
+// Background task that deletes expired revocations, sessions and refresh tokens so those tables stay small
+pub fn spawn_pruning_task(pool: PgPool) {
+    let interval_seconds = parse_or("AUTH_PRUNE_INTERVAL_SECONDS", DEFAULT_PRUNE_INTERVAL_SECONDS);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+        loop {
+            interval.tick().await;
+            match prune_expired_auth_records_repository(&pool).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!(pruned, "Pruned expired auth records"),
+                Err(err) => tracing::error!("Failed to prune expired auth records: {}", err),
+            }
+        }
+    });
+}
@@ -0,0 +1,78 @@
+use crate::config::env::{bool_or, parse_or};
+
+const DEFAULT_PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 60 * 60;
+const DEFAULT_EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 24 * 60 * 60;
+const DEFAULT_MAGIC_LINK_TOKEN_TTL_SECONDS: i64 = 15 * 60;
+const DEFAULT_MAGIC_LINK_MAX_REQUESTS: i64 = 3;
+const DEFAULT_MAGIC_LINK_WINDOW_SECONDS: i64 = 15 * 60;
+const DEFAULT_LOCKOUT_THRESHOLD: i64 = 5;
+const DEFAULT_LOCKOUT_BASE_SECONDS: i64 = 30;
+const DEFAULT_LOCKOUT_MAX_SECONDS: i64 = 60 * 60;
+const DEFAULT_PASSWORD_HISTORY_DEPTH: i64 = 5;
+// Magic link rows are pruned a day after they expire, so a longer window would undercount
+const MAX_MAGIC_LINK_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+// Settings for the account flows under /auth that aren't about tokens or cookies
+pub struct AuthConfig {
+    // Frontend origin used to build links sent by email
+    pub app_base_url: String,
+    pub password_reset_token_ttl_seconds: i64,
+    pub email_verification_token_ttl_seconds: i64,
+    // When false, login is refused until the address has been verified
+    pub allow_unverified_login: bool,
+    pub magic_link_token_ttl_seconds: i64,
+    // At most `magic_link_max_requests` links per address within any `magic_link_window_seconds`
+    pub magic_link_max_requests: i64,
+    pub magic_link_window_seconds: i64,
+    // After `lockout_threshold` consecutive failed logins an address is locked for `lockout_base_seconds`,
+    // doubling with every further failure up to `lockout_max_seconds`
+    pub lockout_threshold: i64,
+    pub lockout_base_seconds: i64,
+    pub lockout_max_seconds: i64,
+    // A new password may not match any of the last `password_history_depth` ones, the current one included
+    pub password_history_depth: i64,
+    // Registration, login and password reset answer the same way, in about the same time, whether or
+    // not the address has an account
+    pub enumeration_safe: bool,
+}
+
+impl AuthConfig {
+    pub fn from_env() -> Self {
+        let magic_link_window_seconds =
+            parse_or("MAGIC_LINK_WINDOW_SECONDS", DEFAULT_MAGIC_LINK_WINDOW_SECONDS);
+        assert!(
+            magic_link_window_seconds <= MAX_MAGIC_LINK_WINDOW_SECONDS,
+            "MAGIC_LINK_WINDOW_SECONDS must be at most {MAX_MAGIC_LINK_WINDOW_SECONDS}"
+        );
+
+        let password_history_depth = parse_or("PASSWORD_HISTORY_DEPTH", DEFAULT_PASSWORD_HISTORY_DEPTH);
+        assert!(password_history_depth >= 0, "PASSWORD_HISTORY_DEPTH must not be negative");
+
+        Self {
+            app_base_url: std::env::var("APP_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:5173".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            password_reset_token_ttl_seconds: parse_or(
+                "PASSWORD_RESET_TOKEN_TTL_SECONDS",
+                DEFAULT_PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            ),
+            email_verification_token_ttl_seconds: parse_or(
+                "EMAIL_VERIFICATION_TOKEN_TTL_SECONDS",
+                DEFAULT_EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+            ),
+            allow_unverified_login: bool_or("ALLOW_UNVERIFIED_LOGIN", true),
+            magic_link_token_ttl_seconds: parse_or(
+                "MAGIC_LINK_TOKEN_TTL_SECONDS",
+                DEFAULT_MAGIC_LINK_TOKEN_TTL_SECONDS,
+            ),
+            magic_link_max_requests: parse_or("MAGIC_LINK_MAX_REQUESTS", DEFAULT_MAGIC_LINK_MAX_REQUESTS),
+            magic_link_window_seconds,
+            lockout_threshold: parse_or("LOCKOUT_THRESHOLD", DEFAULT_LOCKOUT_THRESHOLD),
+            lockout_base_seconds: parse_or("LOCKOUT_BASE_SECONDS", DEFAULT_LOCKOUT_BASE_SECONDS),
+            lockout_max_seconds: parse_or("LOCKOUT_MAX_SECONDS", DEFAULT_LOCKOUT_MAX_SECONDS),
+            password_history_depth,
+            enumeration_safe: bool_or("ENUMERATION_SAFE_MODE", false),
+        }
+    }
+}
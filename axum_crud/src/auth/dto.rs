@@ -0,0 +1,54 @@
+
+use serde::{Deserialize};
+
+#[derive(Deserialize,Debug)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+// Optional body for /auth/logout; bearer clients pass their refresh token so its family is revoked too
+#[derive(Deserialize,Debug)]
+pub struct LogoutRequest {
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+// `email` is optional so the form can be checked before the address is typed in
+#[derive(Deserialize,Debug)]
+pub struct PasswordStrengthRequest {
+    pub password: String,
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+// Query string of /auth/magic_link/consume; `session=true` answers with a session cookie instead of bearer tokens
+#[derive(Deserialize,Debug)]
+pub struct ConsumeMagicLinkQuery {
+    pub token: String,
+    #[serde(default)]
+    pub session: bool,
+}
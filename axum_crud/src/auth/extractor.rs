@@ -0,0 +1,97 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::auth::repository::is_access_token_revoked_repository;
+use crate::auth::session::Session;
+use crate::crud::error_traits::AppError;
+use crate::grouped_routes::main_route::AppState;
+
+// Add `auth: AuthUser` to a handler's arguments to require a valid bearer token
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: i32,
+    pub email: String,
+    pub jti: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts)
+            .ok_or_else(|| AppError::authentication("Missing bearer token"))?;
+
+        let claims = state.jwt.decode_access_token(token)?;
+        let id = claims.credential_id()?;
+        let issued_at = unix_to_datetime(claims.iat)?;
+        let expires_at = unix_to_datetime(claims.exp)?;
+
+        if is_access_token_revoked_repository(&state.db, claims.jti, id, issued_at).await? {
+            return Err(AppError::authentication("Access token has been revoked"));
+        }
+
+        Ok(AuthUser {
+            id,
+            email: claims.email,
+            jti: claims.jti,
+            expires_at,
+        })
+    }
+}
+
+// Either a bearer token or a session cookie, for routes that serve both kinds of client.
+// The Authorization header wins when both are present.
+#[derive(Debug, Clone)]
+pub enum Authenticated {
+    Bearer(AuthUser),
+    Session(Session),
+}
+
+impl Authenticated {
+    pub fn credential_id(&self) -> i32 {
+        match self {
+            Authenticated::Bearer(user) => user.id,
+            Authenticated::Session(session) => session.credential_id,
+        }
+    }
+
+    pub fn email(&self) -> &str {
+        match self {
+            Authenticated::Bearer(user) => &user.email,
+            Authenticated::Session(session) => &session.email,
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for Authenticated {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if parts.headers.contains_key(header::AUTHORIZATION) {
+            AuthUser::from_request_parts(parts, state).await.map(Authenticated::Bearer)
+        } else {
+            Session::from_request_parts(parts, state).await.map(Authenticated::Session)
+        }
+    }
+}
+
+pub fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+fn unix_to_datetime(seconds: u64) -> Result<DateTime<Utc>, AppError> {
+    DateTime::from_timestamp(seconds as i64, 0)
+        .ok_or_else(|| AppError::authentication("Access token is invalid"))
+}
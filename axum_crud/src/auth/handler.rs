@@ -0,0 +1,433 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+    response::{IntoResponse, Response},
+    http::StatusCode,
+};
+use axum_extra::extract::cookie::CookieJar;
+use crate::auth::dto::{
+    RefreshRequest,LogoutRequest,ForgotPasswordRequest,ResetPasswordRequest,PasswordStrengthRequest,
+    VerifyEmailRequest,ResendVerificationRequest,MagicLinkRequest,ConsumeMagicLinkQuery,
+};
+use crate::auth::extractor::Authenticated;
+use crate::auth::model::SessionResponse;
+use crate::auth::services::{
+    issue_token_pair_service,refresh_service,create_session_service,logout_service,logout_all_service,
+    forgot_password_service,reset_password_service,password_strength_service,
+    verify_email_service,resend_verification_service,
+    request_magic_link_service,consume_magic_link_service,
+};
+use crate::auth::session::Session;
+use crate::crud::dto::LoginRequest;
+use crate::crud::error_traits::{AppResult};
+use crate::crud::model::LoginResponse;
+use crate::mfa::services::mfa_challenge_service;
+use crate::crud::services::authenticate_credentials_service;
+use crate::grouped_routes::main_route::AppState;
+
+#[axum::debug_handler]
+pub async fn refresh_handler(
+    State(state): State<AppState>,
+    Json(body): Json<RefreshRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = refresh_service(body, &state.db, &state.jwt).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn session_login_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(body): Json<LoginRequest>,
+) -> AppResult<Response> {
+    let credentials = authenticate_credentials_service(body, &state.db, &state.auth_config, &state.passwords).await?;
+
+    // No cookie until the second factor is in; /auth/mfa/verify with `session: true` sets it
+    if let Some(challenge) = mfa_challenge_service(credentials.id, &credentials.email, &state.db, &state.jwt, &state.totp).await? {
+        let response = LoginResponse::MfaRequired {
+            mfa_token: challenge.mfa_token,
+            expires_in: challenge.expires_in,
+            methods: challenge.methods,
+        };
+        return Ok((StatusCode::OK, Json(response)).into_response());
+    }
+
+    let previous_session_id = jar.get(&state.sessions.cookie_name).map(|cookie| cookie.value().to_string());
+    let (session_id, response) = create_session_service(
+        credentials.id,
+        credentials.email,
+        previous_session_id.as_deref(),
+        &state.db,
+        &state.sessions,
+    )
+    .await?;
+
+    let jar = jar.add(state.sessions.session_cookie(session_id));
+    Ok((StatusCode::OK, jar, Json(response)).into_response())
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn current_session_handler(session: Session) -> AppResult<impl IntoResponse> {
+    Ok((StatusCode::OK, Json(SessionResponse {
+        id: session.credential_id,
+        email: session.email,
+        expires_at: session.expires_at,
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    auth: Authenticated,
+    body: Option<Json<LogoutRequest>>,
+) -> AppResult<impl IntoResponse> {
+    logout_service(&auth, body.map(|Json(body)| body), &state.db).await?;
+
+    let jar = match auth {
+        Authenticated::Session(_) => jar.add(state.sessions.removal_cookie()),
+        Authenticated::Bearer(_) => jar,
+    };
+    Ok((StatusCode::NO_CONTENT, jar))
+}
+
+#[axum::debug_handler]
+pub async fn logout_all_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    logout_all_service(auth.credential_id(), &state.db, &state.jwt).await?;
+
+    let jar = match auth {
+        Authenticated::Session(_) => jar.add(state.sessions.removal_cookie()),
+        Authenticated::Bearer(_) => jar,
+    };
+    Ok((StatusCode::NO_CONTENT, jar))
+}
+
+#[axum::debug_handler]
+pub async fn forgot_password_handler(
+    State(state): State<AppState>,
+    Json(body): Json<ForgotPasswordRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = forgot_password_service(body, &state.db, &state.mailer, &state.auth_config).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn reset_password_handler(
+    State(state): State<AppState>,
+    Json(body): Json<ResetPasswordRequest>,
+) -> AppResult<impl IntoResponse> {
+    reset_password_service(body, &state.db, &state.jwt, &state.auth_config, &state.passwords, &state.password_policy).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+pub async fn password_strength_handler(
+    State(state): State<AppState>,
+    Json(body): Json<PasswordStrengthRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = password_strength_service(body, &state.password_policy).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn verify_email_handler(
+    State(state): State<AppState>,
+    Json(body): Json<VerifyEmailRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = verify_email_service(body, &state.db, &state.jwt).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn resend_verification_handler(
+    State(state): State<AppState>,
+    Json(body): Json<ResendVerificationRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = resend_verification_service(
+        body,
+        &state.db,
+        &state.jwt,
+        &state.mailer,
+        &state.auth_config,
+    )
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn request_magic_link_handler(
+    State(state): State<AppState>,
+    Json(body): Json<MagicLinkRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = request_magic_link_service(body, &state.db, &state.mailer, &state.auth_config).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+// The link replaces the password only; accounts with 2FA still go through /auth/mfa/verify
+#[axum::debug_handler]
+pub async fn consume_magic_link_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<ConsumeMagicLinkQuery>,
+) -> AppResult<Response> {
+    let consumed = consume_magic_link_service(&query.token, &state.db).await?;
+
+    if let Some(challenge) = mfa_challenge_service(consumed.credential_id, &consumed.email, &state.db, &state.jwt, &state.totp).await? {
+        let response = LoginResponse::MfaRequired {
+            mfa_token: challenge.mfa_token,
+            expires_in: challenge.expires_in,
+            methods: challenge.methods,
+        };
+        return Ok((StatusCode::OK, Json(response)).into_response());
+    }
+
+    completed_login_response(&state, jar, consumed.credential_id, consumed.email, query.session).await
+}
+
+// Final step of the multi-step logins (2FA, passkeys, magic links): bearer tokens, or a session cookie when asked for
+pub async fn completed_login_response(
+    state: &AppState,
+    jar: CookieJar,
+    credential_id: i32,
+    email: String,
+    session: bool,
+) -> AppResult<Response> {
+    if session {
+        let previous_session_id = jar.get(&state.sessions.cookie_name).map(|cookie| cookie.value().to_string());
+        let (session_id, response) = create_session_service(
+            credential_id,
+            email,
+            previous_session_id.as_deref(),
+            &state.db,
+            &state.sessions,
+        )
+        .await?;
+
+        let jar = jar.add(state.sessions.session_cookie(session_id));
+        return Ok((StatusCode::OK, jar, Json(response)).into_response());
+    }
+
+    let tokens = issue_token_pair_service(credential_id, &email, &state.db, &state.jwt).await?;
+    Ok((StatusCode::OK, Json(tokens)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{header, Method, Request, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::api_keys::extractor::API_KEY_HEADER;
+    use crate::auth::config::AuthConfig;
+    use crate::grouped_routes::main_route::AppState;
+    use crate::test_support::{json_request, send, send_json, test_state, wait_for_mail, RecordingMailer, TestResponse};
+
+    const KNOWN: &str = "known@example.com";
+    const UNKNOWN: &str = "unknown@example.com";
+    const PASSWORD: &str = "Correct-Horse-Battery-9";
+    const NEW_PASSWORD: &str = "Fresh-Staple-Lantern-4";
+
+    async fn safe_state(pool: PgPool) -> (AppState, Arc<RecordingMailer>) {
+        registered_state(pool, |config| config.enumeration_safe = true).await
+    }
+
+    // State with KNOWN already registered and its welcome mail cleared
+    async fn registered_state(pool: PgPool, configure: impl FnOnce(&mut AuthConfig)) -> (AppState, Arc<RecordingMailer>) {
+        let mailer = Arc::new(RecordingMailer::default());
+        let state = test_state(pool, mailer.clone(), configure);
+
+        let registered = send_json(&state, Method::POST, "/credentials", json!({ "email": KNOWN, "password": PASSWORD })).await;
+        assert!(registered.status.is_success(), "registration failed: {:?}", registered.body);
+        wait_for_mail(&mailer, KNOWN).await;
+        mailer.clear();
+
+        (state, mailer)
+    }
+
+    fn assert_indistinguishable(known: &TestResponse, unknown: &TestResponse) {
+        assert_eq!(known.status, unknown.status);
+        assert_eq!(known.headers, unknown.headers);
+        assert_eq!(known.body, unknown.body);
+    }
+
+    #[sqlx::test]
+    async fn registration_answers_the_same_for_taken_and_new_emails(pool: PgPool) {
+        let (state, mailer) = safe_state(pool).await;
+
+        let known = send_json(&state, Method::POST, "/credentials", json!({ "email": KNOWN, "password": PASSWORD })).await;
+        let unknown = send_json(&state, Method::POST, "/credentials", json!({ "email": UNKNOWN, "password": PASSWORD })).await;
+
+        assert_indistinguishable(&known, &unknown);
+        // The owner of the taken address hears about the attempt instead
+        assert_eq!(wait_for_mail(&mailer, KNOWN).await.len(), 1);
+        assert_eq!(wait_for_mail(&mailer, UNKNOWN).await[0].subject, "Verify your email address");
+    }
+
+    #[sqlx::test]
+    async fn login_answers_the_same_for_a_wrong_password_and_an_unknown_email(pool: PgPool) {
+        let (state, _) = safe_state(pool).await;
+
+        let known = send_json(&state, Method::POST, "/crud/login", json!({ "email": KNOWN, "password": "Wrong-Password-1" })).await;
+        let unknown = send_json(&state, Method::POST, "/crud/login", json!({ "email": UNKNOWN, "password": "Wrong-Password-1" })).await;
+
+        assert_indistinguishable(&known, &unknown);
+    }
+
+    #[sqlx::test]
+    async fn forgot_password_answers_the_same_for_known_and_unknown_emails(pool: PgPool) {
+        let (state, mailer) = safe_state(pool).await;
+
+        let known = send_json(&state, Method::POST, "/auth/forgot_password", json!({ "email": KNOWN })).await;
+        let unknown = send_json(&state, Method::POST, "/auth/forgot_password", json!({ "email": UNKNOWN })).await;
+
+        assert_indistinguishable(&known, &unknown);
+        assert_eq!(wait_for_mail(&mailer, KNOWN).await[0].subject, "Reset your password");
+        assert!(mailer.sent_to(UNKNOWN).is_empty());
+    }
+
+    #[sqlx::test]
+    async fn forgot_password_issues_the_reset_token_after_responding(pool: PgPool) {
+        let (state, mailer) = safe_state(pool).await;
+
+        let known = send_json(&state, Method::POST, "/auth/forgot_password", json!({ "email": KNOWN })).await;
+        let unknown = send_json(&state, Method::POST, "/auth/forgot_password", json!({ "email": UNKNOWN })).await;
+        assert_indistinguishable(&known, &unknown);
+
+        // The token is written in the background, before its mail goes out, and still resets the password
+        let email = wait_for_mail(&mailer, KNOWN).await.pop().unwrap();
+        let (_, token) = email.body.split_once("token=").unwrap();
+        let token = token.split_whitespace().next().unwrap();
+        assert_eq!(reset_password(&state, token).await.status, StatusCode::NO_CONTENT);
+
+        let issued = sqlx::query_scalar!("SELECT COUNT(*) AS \"count!\" FROM password_reset_tokens")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(issued, 1);
+    }
+
+    #[sqlx::test]
+    async fn resend_verification_answers_the_same_for_known_and_unknown_emails(pool: PgPool) {
+        let (state, mailer) = safe_state(pool).await;
+
+        let known = send_json(&state, Method::POST, "/auth/resend_verification", json!({ "email": KNOWN })).await;
+        let unknown = send_json(&state, Method::POST, "/auth/resend_verification", json!({ "email": UNKNOWN })).await;
+
+        assert_indistinguishable(&known, &unknown);
+        assert_eq!(wait_for_mail(&mailer, KNOWN).await[0].subject, "Verify your email address");
+        assert!(mailer.sent_to(UNKNOWN).is_empty());
+    }
+
+    #[sqlx::test]
+    async fn magic_link_answers_the_same_for_known_and_unknown_emails(pool: PgPool) {
+        let (state, mailer) = safe_state(pool).await;
+
+        let known = send_json(&state, Method::POST, "/auth/magic_link", json!({ "email": KNOWN })).await;
+        let unknown = send_json(&state, Method::POST, "/auth/magic_link", json!({ "email": UNKNOWN })).await;
+
+        assert_indistinguishable(&known, &unknown);
+        assert_eq!(wait_for_mail(&mailer, KNOWN).await[0].subject, "Your login link");
+        assert!(mailer.sent_to(UNKNOWN).is_empty());
+    }
+
+    // The token from the most recent reset mail
+    async fn reset_token(state: &AppState, mailer: &RecordingMailer) -> String {
+        let requested = send_json(state, Method::POST, "/auth/forgot_password", json!({ "email": KNOWN })).await;
+        assert_eq!(requested.status, StatusCode::ACCEPTED);
+
+        let email = wait_for_mail(mailer, KNOWN).await.pop().unwrap();
+        mailer.clear();
+        let (_, token) = email.body.split_once("token=").unwrap();
+        token.split_whitespace().next().unwrap().to_string()
+    }
+
+    async fn reset_password(state: &AppState, token: &str) -> TestResponse {
+        send_json(state, Method::POST, "/auth/reset_password", json!({ "token": token, "new_password": NEW_PASSWORD })).await
+    }
+
+    async fn login(state: &AppState, password: &str) -> TestResponse {
+        send_json(state, Method::POST, "/crud/login", json!({ "email": KNOWN, "password": password })).await
+    }
+
+    fn get(uri: &str, header_name: header::HeaderName, value: String) -> Request<Body> {
+        Request::builder().uri(uri).header(header_name, value).body(Body::empty()).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn reset_token_works_only_once(pool: PgPool) {
+        let (state, mailer) = registered_state(pool, |config| config.allow_unverified_login = true).await;
+        let token = reset_token(&state, &mailer).await;
+
+        assert_eq!(reset_password(&state, &token).await.status, StatusCode::NO_CONTENT);
+        assert_eq!(login(&state, NEW_PASSWORD).await.status, StatusCode::OK);
+        assert_eq!(login(&state, PASSWORD).await.status, StatusCode::UNAUTHORIZED);
+
+        let replayed = reset_password(&state, &token).await;
+        assert_eq!(replayed.status, StatusCode::BAD_REQUEST);
+        assert_eq!(replayed.json()["message"], "Password reset token is invalid or has expired");
+    }
+
+    #[sqlx::test]
+    async fn expired_reset_token_is_rejected(pool: PgPool) {
+        let (state, mailer) = registered_state(pool, |config| config.allow_unverified_login = true).await;
+        let token = reset_token(&state, &mailer).await;
+        sqlx::query!("UPDATE password_reset_tokens SET expires_at = NOW() - INTERVAL '1 second'")
+            .execute(&state.db)
+            .await
+            .unwrap();
+
+        let expired = reset_password(&state, &token).await;
+
+        assert_eq!(expired.status, StatusCode::BAD_REQUEST);
+        assert_eq!(login(&state, PASSWORD).await.status, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn reset_signs_the_account_out_everywhere(pool: PgPool) {
+        let (state, mailer) = registered_state(pool, |config| config.allow_unverified_login = true).await;
+        let tokens = login(&state, PASSWORD).await.json();
+        let access_token = tokens["access_token"].as_str().unwrap().to_string();
+        let refresh_token = tokens["refresh_token"].as_str().unwrap().to_string();
+        let session = send_json(&state, Method::POST, "/auth/session/login", json!({ "email": KNOWN, "password": PASSWORD })).await;
+        let cookie = session.cookie();
+        let mut create_key = json_request(Method::POST, "/auth/api_keys", json!({ "name": "nightly-export" }));
+        create_key.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {access_token}").parse().unwrap());
+        let api_key = send(&state, create_key).await.json()["key"].as_str().unwrap().to_string();
+
+        let me = get("/crud/me", header::AUTHORIZATION, format!("Bearer {access_token}"));
+        assert_eq!(send(&state, me).await.status, StatusCode::OK);
+        assert_eq!(send(&state, get("/auth/session", header::COOKIE, cookie.clone())).await.status, StatusCode::OK);
+        let current_key = get("/auth/api_keys/current", API_KEY_HEADER.parse().unwrap(), api_key.clone());
+        assert_eq!(send(&state, current_key).await.status, StatusCode::OK);
+
+        let token = reset_token(&state, &mailer).await;
+        assert_eq!(reset_password(&state, &token).await.status, StatusCode::NO_CONTENT);
+
+        let me = get("/crud/me", header::AUTHORIZATION, format!("Bearer {access_token}"));
+        assert_eq!(send(&state, me).await.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&state, get("/auth/session", header::COOKIE, cookie)).await.status, StatusCode::UNAUTHORIZED);
+        let refreshed = send_json(&state, Method::POST, "/auth/refresh", json!({ "refresh_token": refresh_token })).await;
+        assert_eq!(refreshed.status, StatusCode::UNAUTHORIZED);
+        let current_key = get("/auth/api_keys/current", API_KEY_HEADER.parse().unwrap(), api_key);
+        assert_eq!(send(&state, current_key).await.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn reset_refuses_a_recent_password_and_keeps_the_token(pool: PgPool) {
+        let (state, mailer) = registered_state(pool, |config| config.allow_unverified_login = true).await;
+        let token = reset_token(&state, &mailer).await;
+
+        let reused = send_json(&state, Method::POST, "/auth/reset_password", json!({ "token": token, "new_password": PASSWORD })).await;
+        assert_eq!(reused.status, StatusCode::BAD_REQUEST);
+        assert_eq!(reused.json()["error"], "PASSWORD_REUSED");
+
+        assert_eq!(reset_password(&state, &token).await.status, StatusCode::NO_CONTENT);
+    }
+}
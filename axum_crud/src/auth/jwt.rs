@@ -0,0 +1,231 @@
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, get_current_timestamp, Algorithm, DecodingKey, EncodingKey,
+    Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::config::env::parse_or;
+
+const DEFAULT_ACCESS_TOKEN_TTL_SECONDS: u64 = 15 * 60;
+const DEFAULT_REFRESH_TOKEN_TTL_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+// Claims carried by every access token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub email: String,
+    pub jti: Uuid,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+impl Claims {
+    pub fn credential_id(&self) -> AppResult<i32> {
+        self.sub
+            .parse()
+            .map_err(|_| AppError::authentication("Invalid token subject"))
+    }
+}
+
+// Claims for single-purpose tokens (email verification links etc). They carry no `jti`, so they
+// can never be decoded as access tokens, and access tokens lack `purpose`, so the reverse fails too.
+// Single-use tokens carry their id as `nonce` instead, which keeps that guarantee.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurposeClaims {
+    pub sub: String,
+    pub email: String,
+    pub purpose: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Uuid>,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+impl PurposeClaims {
+    pub fn credential_id(&self) -> AppResult<i32> {
+        self.sub
+            .parse()
+            .map_err(|_| AppError::authentication("Invalid token subject"))
+    }
+}
+
+pub struct IssuedToken {
+    pub token: String,
+    pub jti: Uuid,
+    pub expires_in: u64,
+}
+
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    access_token_ttl_seconds: u64,
+    refresh_token_ttl_seconds: u64,
+}
+
+impl JwtKeys {
+    // JWT_ALGORITHM selects HS256 (JWT_SECRET) or RS256/EdDSA (JWT_PRIVATE_KEY_PATH + JWT_PUBLIC_KEY_PATH, PEM)
+    pub fn from_env() -> Self {
+        let algorithm = std::env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string());
+
+        let (algorithm, encoding, decoding) = match algorithm.as_str() {
+            "HS256" => {
+                let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+                (
+                    Algorithm::HS256,
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    DecodingKey::from_secret(secret.as_bytes()),
+                )
+            }
+            "RS256" => {
+                let (private_pem, public_pem) = read_key_pair();
+                (
+                    Algorithm::RS256,
+                    EncodingKey::from_rsa_pem(&private_pem).expect("JWT_PRIVATE_KEY_PATH is not a valid RSA PEM"),
+                    DecodingKey::from_rsa_pem(&public_pem).expect("JWT_PUBLIC_KEY_PATH is not a valid RSA PEM"),
+                )
+            }
+            "EdDSA" => {
+                let (private_pem, public_pem) = read_key_pair();
+                (
+                    Algorithm::EdDSA,
+                    EncodingKey::from_ed_pem(&private_pem).expect("JWT_PRIVATE_KEY_PATH is not a valid Ed25519 PEM"),
+                    DecodingKey::from_ed_pem(&public_pem).expect("JWT_PUBLIC_KEY_PATH is not a valid Ed25519 PEM"),
+                )
+            }
+            other => panic!("Unsupported JWT_ALGORITHM: {other} (expected HS256, RS256 or EdDSA)"),
+        };
+
+        let access_token_ttl_seconds = parse_or("JWT_ACCESS_TOKEN_TTL_SECONDS", DEFAULT_ACCESS_TOKEN_TTL_SECONDS);
+        let refresh_token_ttl_seconds = parse_or("REFRESH_TOKEN_TTL_SECONDS", DEFAULT_REFRESH_TOKEN_TTL_SECONDS);
+
+        Self {
+            algorithm,
+            encoding,
+            decoding,
+            access_token_ttl_seconds,
+            refresh_token_ttl_seconds,
+        }
+    }
+
+    pub fn access_token_ttl_seconds(&self) -> u64 {
+        self.access_token_ttl_seconds
+    }
+
+    pub fn refresh_token_ttl_seconds(&self) -> u64 {
+        self.refresh_token_ttl_seconds
+    }
+
+    pub fn issue_access_token(&self, credential_id: i32, email: &str) -> AppResult<IssuedToken> {
+        let now = get_current_timestamp();
+        let jti = Uuid::new_v4();
+        let claims = Claims {
+            sub: credential_id.to_string(),
+            email: email.to_string(),
+            jti,
+            iat: now,
+            exp: now + self.access_token_ttl_seconds,
+        };
+
+        let token = encode(&Header::new(self.algorithm), &claims, &self.encoding)
+            .map_err(|err| AppError::internal(format!("Failed to sign access token: {err}")))?;
+
+        Ok(IssuedToken {
+            token,
+            jti,
+            expires_in: self.access_token_ttl_seconds,
+        })
+    }
+
+    pub fn decode_access_token(&self, token: &str) -> AppResult<Claims> {
+        let mut validation = Validation::new(self.algorithm);
+        validation.leeway = 0;
+
+        decode::<Claims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ExpiredSignature => AppError::authentication("Access token has expired"),
+                ErrorKind::InvalidSignature => AppError::authentication("Access token signature is invalid"),
+                _ => AppError::authentication("Access token is invalid"),
+            })
+    }
+}
+
+impl JwtKeys {
+    pub fn issue_purpose_token(
+        &self,
+        purpose: &str,
+        credential_id: i32,
+        email: &str,
+        ttl_seconds: u64,
+    ) -> AppResult<String> {
+        self.sign_purpose_token(purpose, credential_id, email, None, ttl_seconds)
+    }
+
+    // The caller stores the returned id and checks it on use, see `PurposeClaims::nonce`
+    pub fn issue_single_use_purpose_token(
+        &self,
+        purpose: &str,
+        credential_id: i32,
+        email: &str,
+        ttl_seconds: u64,
+    ) -> AppResult<(String, Uuid)> {
+        let nonce = Uuid::new_v4();
+        let token = self.sign_purpose_token(purpose, credential_id, email, Some(nonce), ttl_seconds)?;
+        Ok((token, nonce))
+    }
+
+    fn sign_purpose_token(
+        &self,
+        purpose: &str,
+        credential_id: i32,
+        email: &str,
+        nonce: Option<Uuid>,
+        ttl_seconds: u64,
+    ) -> AppResult<String> {
+        let now = get_current_timestamp();
+        let claims = PurposeClaims {
+            sub: credential_id.to_string(),
+            email: email.to_string(),
+            purpose: purpose.to_string(),
+            nonce,
+            iat: now,
+            exp: now + ttl_seconds,
+        };
+
+        encode(&Header::new(self.algorithm), &claims, &self.encoding)
+            .map_err(|err| AppError::internal(format!("Failed to sign {purpose} token: {err}")))
+    }
+
+    pub fn decode_purpose_token(&self, purpose: &str, token: &str) -> AppResult<PurposeClaims> {
+        let mut validation = Validation::new(self.algorithm);
+        validation.leeway = 0;
+
+        let claims = decode::<PurposeClaims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ExpiredSignature => AppError::authentication("Token has expired"),
+                _ => AppError::authentication("Token is invalid"),
+            })?;
+
+        if claims.purpose != purpose {
+            return Err(AppError::authentication("Token is invalid"));
+        }
+
+        Ok(claims)
+    }
+}
+
+fn read_key_pair() -> (Vec<u8>, Vec<u8>) {
+    let private_path = std::env::var("JWT_PRIVATE_KEY_PATH").expect("JWT_PRIVATE_KEY_PATH must be set");
+    let public_path = std::env::var("JWT_PUBLIC_KEY_PATH").expect("JWT_PUBLIC_KEY_PATH must be set");
+
+    let private_pem = std::fs::read(&private_path)
+        .unwrap_or_else(|err| panic!("Failed to read {private_path}: {err}"));
+    let public_pem = std::fs::read(&public_path)
+        .unwrap_or_else(|err| panic!("Failed to read {public_path}: {err}"));
+
+    (private_pem, public_pem)
+}
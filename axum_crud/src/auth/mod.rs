@@ -0,0 +1,12 @@
+pub mod config;
+pub mod jwt;
+pub mod extractor;
+pub mod session;
+pub mod tokens;
+pub mod dto;
+pub mod model;
+pub mod repository;
+pub mod services;
+pub mod handler;
+pub mod routes;
+pub mod cleanup;
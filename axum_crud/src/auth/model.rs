@@ -0,0 +1,47 @@
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize};
+use uuid::Uuid;
+
+
+#[derive(Serialize)]
+pub struct MessageResponse {
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    pub id: i32,
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct TokenPairResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+}
+
+pub struct StoredPasswordResetToken {
+    pub id: i32,
+    pub credential_id: i32,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+pub struct ConsumedMagicLink {
+    pub credential_id: i32,
+    pub email: String,
+}
+
+pub struct StoredRefreshToken {
+    pub id: i32,
+    pub credential_id: i32,
+    pub email: String,
+    pub family_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,533 @@
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::auth::model::{StoredRefreshToken, StoredPasswordResetToken, ConsumedMagicLink};
+use crate::auth::session::Session;
+use crate::crud::error_traits::AppResult;
+
+
+pub async fn insert_refresh_token_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    family_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (credential_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        credential_id,
+        family_id,
+        token_hash,
+        expires_at
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// Locks the row so two concurrent refreshes with the same token can't both rotate it
+pub async fn find_refresh_token_for_update_repository(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> AppResult<Option<StoredRefreshToken>> {
+    let record = sqlx::query!(
+        r#"
+        SELECT rt.id, rt.credential_id, c.email, rt.family_id, rt.expires_at, rt.used_at, rt.revoked_at
+        FROM refresh_tokens rt
+        JOIN credentials c ON c.id = rt.credential_id
+        WHERE rt.token_hash = $1
+        FOR UPDATE OF rt
+        "#,
+        token_hash
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(record.map(|r| StoredRefreshToken {
+        id: r.id,
+        credential_id: r.credential_id,
+        email: r.email,
+        family_id: r.family_id,
+        expires_at: r.expires_at,
+        used_at: r.used_at,
+        revoked_at: r.revoked_at,
+    }))
+}
+
+pub async fn mark_refresh_token_used_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1",
+        id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn revoke_refresh_token_family_repository(
+    executor: impl PgExecutor<'_>,
+    family_id: Uuid,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+        family_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+
+pub async fn insert_session_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    session_hash: &str,
+    expires_at: DateTime<Utc>,
+    absolute_expires_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO sessions (credential_id, session_hash, expires_at, absolute_expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        credential_id,
+        session_hash,
+        expires_at,
+        absolute_expires_at
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_session_repository(
+    executor: impl PgExecutor<'_>,
+    session_hash: &str,
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM sessions WHERE session_hash = $1",
+        session_hash
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// Sliding expiry: a live session is pushed forward by the idle timeout, never past its absolute expiry
+pub async fn touch_session_repository(
+    executor: impl PgExecutor<'_>,
+    session_hash: &str,
+    idle_timeout_seconds: i64,
+) -> AppResult<Option<Session>> {
+    let record = sqlx::query!(
+        r#"
+        WITH touched AS (
+            UPDATE sessions
+            SET last_seen_at = NOW(),
+                expires_at = LEAST(NOW() + make_interval(secs => $2), absolute_expires_at)
+            WHERE session_hash = $1 AND expires_at > NOW()
+            RETURNING credential_id, session_hash, expires_at
+        )
+        SELECT t.credential_id AS "credential_id!", t.session_hash AS "session_hash!",
+               t.expires_at AS "expires_at!", c.email AS "email!"
+        FROM touched t
+        JOIN credentials c ON c.id = t.credential_id
+        "#,
+        session_hash,
+        idle_timeout_seconds as f64
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|r| Session {
+        credential_id: r.credential_id,
+        email: r.email,
+        session_hash: r.session_hash,
+        expires_at: r.expires_at,
+    }))
+}
+
+
+pub async fn delete_sessions_for_credential_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM sessions WHERE credential_id = $1",
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// Everything but the session making the request
+pub async fn delete_other_sessions_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    keep_session_hash: &str,
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM sessions WHERE credential_id = $1 AND session_hash <> $2",
+        credential_id,
+        keep_session_hash
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn revoke_refresh_tokens_for_credential_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE credential_id = $1 AND revoked_at IS NULL",
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// Revokes a single refresh token family, but only if it belongs to `credential_id`
+pub async fn revoke_refresh_token_family_by_token_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    token_hash: &str,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE revoked_at IS NULL
+          AND family_id = (
+              SELECT family_id FROM refresh_tokens WHERE token_hash = $1 AND credential_id = $2
+          )
+        "#,
+        token_hash,
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn revoke_access_token_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    jti: Uuid,
+    expires_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO token_revocations (credential_id, jti, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (jti) DO NOTHING
+        "#,
+        credential_id,
+        jti,
+        expires_at
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// Revokes every access token issued so far; the row can go once the newest of them has expired.
+// `iat` only has whole-second precision and the cutoff is compared with `>=`, so any token issued later
+// in the same wall-clock second is revoked too and stays so for its full TTL. A caller that hands out a
+// fresh token alongside the revocation passes its jti as `exempt_jti`; callers passing None (logout_all,
+// reset) accept that a login in the same second has to be repeated
+pub async fn revoke_access_tokens_issued_before_now_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    access_token_ttl_seconds: i64,
+    exempt_jti: Option<Uuid>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO token_revocations (credential_id, issued_before, expires_at, exempt_jti)
+        VALUES ($1, NOW(), NOW() + make_interval(secs => $2), $3)
+        "#,
+        credential_id,
+        access_token_ttl_seconds as f64,
+        exempt_jti
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+// `iat` only has second precision, so a token minted in the same second as a logout_all counts as revoked,
+// except the one the revocation explicitly exempts
+pub async fn is_access_token_revoked_repository(
+    executor: impl PgExecutor<'_>,
+    jti: Uuid,
+    credential_id: i32,
+    issued_at: DateTime<Utc>,
+) -> AppResult<bool> {
+    let revoked = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM token_revocations
+            WHERE expires_at > NOW()
+              AND (jti = $1 OR (credential_id = $2 AND issued_before >= $3 AND exempt_jti IS DISTINCT FROM $1))
+        ) AS "revoked!"
+        "#,
+        jti,
+        credential_id,
+        issued_at
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(revoked)
+}
+
+pub async fn prune_expired_auth_records_repository(
+    pool: &PgPool,
+) -> AppResult<u64> {
+    let revocations = sqlx::query!("DELETE FROM token_revocations WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    let sessions = sqlx::query!("DELETE FROM sessions WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    let refresh_tokens = sqlx::query!("DELETE FROM refresh_tokens WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    let reset_tokens = sqlx::query!("DELETE FROM password_reset_tokens WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    let webauthn_challenges = sqlx::query!("DELETE FROM webauthn_challenges WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    let mfa_pending_logins = sqlx::query!("DELETE FROM mfa_pending_logins WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    // Failures older than a day no longer count towards a lock
+    let login_attempts = sqlx::query!(
+        "DELETE FROM login_attempts WHERE last_failed_at < NOW() - INTERVAL '1 day' AND (locked_until IS NULL OR locked_until <= NOW())"
+    )
+    .execute(pool)
+    .await?;
+    // Kept a day past expiry so the per-address rate limit window still sees them
+    let magic_links = sqlx::query!("DELETE FROM magic_link_tokens WHERE expires_at <= NOW() - INTERVAL '1 day'")
+        .execute(pool)
+        .await?;
+
+    Ok(revocations.rows_affected()
+        + sessions.rows_affected()
+        + refresh_tokens.rows_affected()
+        + reset_tokens.rows_affected()
+        + webauthn_challenges.rows_affected()
+        + mfa_pending_logins.rows_affected()
+        + magic_links.rows_affected()
+        + login_attempts.rows_affected())
+}
+
+
+// Issuing a new reset token retires any earlier ones so only the latest email works
+pub async fn replace_password_reset_token_repository(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE password_reset_tokens SET used_at = NOW() WHERE credential_id = $1 AND used_at IS NULL",
+        credential_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (credential_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        credential_id,
+        token_hash,
+        expires_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_password_reset_token_repository(
+    executor: impl PgExecutor<'_>,
+    token_hash: &str,
+) -> AppResult<Option<StoredPasswordResetToken>> {
+    let record = sqlx::query!(
+        r#"
+        SELECT id, credential_id, expires_at, used_at
+        FROM password_reset_tokens
+        WHERE token_hash = $1
+        "#,
+        token_hash
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|r| StoredPasswordResetToken {
+        id: r.id,
+        credential_id: r.credential_id,
+        expires_at: r.expires_at,
+        used_at: r.used_at,
+    }))
+}
+
+// False when the token was used or expired in the meantime
+pub async fn consume_password_reset_token_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        "UPDATE password_reset_tokens SET used_at = NOW() WHERE id = $1 AND used_at IS NULL AND expires_at > NOW()",
+        id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Serialises magic link requests for one address until the transaction ends, so the rate limit can't be raced
+pub async fn lock_magic_link_address_repository(
+    conn: &mut PgConnection,
+    email: &str,
+) -> AppResult<()> {
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtext($1))", email)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(())
+}
+
+// Number of requests for the address since `since`, and when the oldest of them was made
+pub async fn recent_magic_link_requests_repository(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+    since: DateTime<Utc>,
+) -> AppResult<(i64, Option<DateTime<Utc>>)> {
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!", MIN(created_at) AS oldest
+        FROM magic_link_tokens
+        WHERE email = $1 AND created_at > $2
+        "#,
+        email,
+        since
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok((record.count, record.oldest))
+}
+
+// Unknown addresses are recorded without a credential or token; a new link retires any earlier ones
+pub async fn insert_magic_link_token_repository(
+    conn: &mut PgConnection,
+    email: &str,
+    credential_id: Option<i32>,
+    token_hash: Option<&str>,
+    expires_at: DateTime<Utc>,
+) -> AppResult<()> {
+    if let Some(credential_id) = credential_id {
+        sqlx::query!(
+            "UPDATE magic_link_tokens SET used_at = NOW() WHERE credential_id = $1 AND used_at IS NULL",
+            credential_id
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO magic_link_tokens (email, credential_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        email,
+        credential_id,
+        token_hash,
+        expires_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// Marks the link used in the same statement that checks it, so a link can only ever be exchanged once
+pub async fn consume_magic_link_token_repository(
+    executor: impl PgExecutor<'_>,
+    token_hash: &str,
+) -> AppResult<Option<ConsumedMagicLink>> {
+    let record = sqlx::query!(
+        r#"
+        UPDATE magic_link_tokens
+        SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW() AND credential_id IS NOT NULL
+        RETURNING credential_id AS "credential_id!", email
+        "#,
+        token_hash
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|r| ConsumedMagicLink {
+        credential_id: r.credential_id,
+        email: r.email,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, DurationRound};
+
+    use super::*;
+    use crate::test_support::create_account;
+
+    #[sqlx::test]
+    async fn a_cutoff_revokes_tokens_up_to_its_second_except_the_exempt_one(pool: PgPool) {
+        let id = create_account(&pool, "cutoff@example.com").await;
+        let other_id = create_account(&pool, "other@example.com").await;
+        let exempt = Uuid::new_v4();
+        // `iat` has whole-second precision
+        let this_second = Utc::now().duration_trunc(Duration::seconds(1)).unwrap();
+
+        revoke_access_tokens_issued_before_now_repository(&pool, id, 900, Some(exempt)).await.unwrap();
+
+        let revoked = |jti, credential_id, issued_at| is_access_token_revoked_repository(&pool, jti, credential_id, issued_at);
+        assert!(revoked(Uuid::new_v4(), id, this_second - Duration::seconds(5)).await.unwrap());
+        assert!(revoked(Uuid::new_v4(), id, this_second).await.unwrap());
+        assert!(!revoked(exempt, id, this_second).await.unwrap());
+        assert!(!revoked(Uuid::new_v4(), id, this_second + Duration::seconds(2)).await.unwrap());
+        assert!(!revoked(Uuid::new_v4(), other_id, this_second).await.unwrap());
+
+        // A later cutoff without an exemption catches the previously exempt token too
+        revoke_access_tokens_issued_before_now_repository(&pool, id, 900, None).await.unwrap();
+        assert!(revoked(exempt, id, this_second).await.unwrap());
+    }
+}
@@ -0,0 +1,27 @@
+use axum::{
+    routing::{get, post},
+    Router
+};
+use crate::auth::handler::{
+    refresh_handler,session_login_handler,current_session_handler,logout_handler,logout_all_handler,
+    forgot_password_handler,reset_password_handler,password_strength_handler,
+    verify_email_handler,resend_verification_handler,
+    request_magic_link_handler,consume_magic_link_handler,
+};
+use crate::grouped_routes::main_route::AppState;
+
+pub fn auth_routes() -> Router<AppState> {
+    Router::new()
+      .route("/refresh", post(refresh_handler))
+      .route("/session/login", post(session_login_handler))
+      .route("/session", get(current_session_handler))
+      .route("/logout", post(logout_handler))
+      .route("/logout_all", post(logout_all_handler))
+      .route("/forgot_password", post(forgot_password_handler))
+      .route("/reset_password", post(reset_password_handler))
+      .route("/password_strength", post(password_strength_handler))
+      .route("/verify_email", post(verify_email_handler))
+      .route("/resend_verification", post(resend_verification_handler))
+      .route("/magic_link", post(request_magic_link_handler))
+      .route("/magic_link/consume", get(consume_magic_link_handler))
+}
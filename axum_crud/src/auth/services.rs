@@ -0,0 +1,514 @@
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::api_keys::repository::revoke_api_keys_for_credential_repository;
+use crate::auth::config::AuthConfig;
+use crate::auth::dto::{
+    RefreshRequest, LogoutRequest, ForgotPasswordRequest, ResetPasswordRequest,
+    VerifyEmailRequest, ResendVerificationRequest, MagicLinkRequest, PasswordStrengthRequest,
+};
+use crate::auth::extractor::Authenticated;
+use crate::auth::jwt::{IssuedToken, JwtKeys};
+use crate::auth::model::{ConsumedMagicLink, MessageResponse, SessionResponse, TokenPairResponse};
+use crate::auth::repository::{
+    consume_magic_link_token_repository, delete_other_sessions_repository, delete_session_repository,
+    delete_sessions_for_credential_repository,
+    find_password_reset_token_repository, find_refresh_token_for_update_repository,
+    insert_magic_link_token_repository, insert_refresh_token_repository, insert_session_repository,
+    lock_magic_link_address_repository,
+    consume_password_reset_token_repository, mark_refresh_token_used_repository,
+    recent_magic_link_requests_repository, replace_password_reset_token_repository,
+    revoke_access_token_repository, revoke_access_tokens_issued_before_now_repository,
+    revoke_refresh_token_family_by_token_repository, revoke_refresh_token_family_repository,
+    revoke_refresh_tokens_for_credential_repository,
+};
+use crate::auth::session::SessionConfig;
+use crate::auth::tokens::{generate_token, hash_token};
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::repository::{
+    get_stored_credentials_by_email_repository, get_stored_credentials_by_id_repository, mark_email_verified_repository,
+    update_password_repository,
+};
+use crate::crud::services::validate_email;
+use crate::passwords::pool::HashingPool;
+use crate::passwords::policy::{PasswordEvaluation, PasswordPolicy};
+use crate::passwords::services::{ensure_password_not_reused_service, record_password_history_service};
+use crate::mailer::{send_in_background, Email, Mailer};
+
+
+// Stores a fresh refresh token in `family_id` and returns the raw value for the client
+async fn store_refresh_token(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    family_id: Uuid,
+    jwt: &JwtKeys,
+) -> AppResult<String> {
+    let refresh_token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(jwt.refresh_token_ttl_seconds() as i64);
+
+    insert_refresh_token_repository(executor, credential_id, family_id, &hash_token(&refresh_token), expires_at).await?;
+
+    Ok(refresh_token)
+}
+
+// Starts a new token family, used on every successful login
+pub async fn issue_token_pair_service(
+    credential_id: i32,
+    email: &str,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+) -> AppResult<TokenPairResponse> {
+    let access_token = jwt.issue_access_token(credential_id, email)?;
+    complete_token_pair_service(access_token, credential_id, pool, jwt).await
+}
+
+// Pairs an already issued access token with a new refresh token family
+pub async fn complete_token_pair_service(
+    access_token: IssuedToken,
+    credential_id: i32,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+) -> AppResult<TokenPairResponse> {
+    let refresh_token = store_refresh_token(pool, credential_id, Uuid::new_v4(), jwt).await?;
+
+    Ok(TokenPairResponse {
+        access_token: access_token.token,
+        refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in: access_token.expires_in,
+    })
+}
+
+pub async fn refresh_service(
+    input: RefreshRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+) -> AppResult<TokenPairResponse> {
+    let mut tx = pool.begin().await?;
+
+    let stored = match find_refresh_token_for_update_repository(&mut tx, &hash_token(&input.refresh_token)).await? {
+        Some(stored) => stored,
+        None => return Err(AppError::authentication("Invalid refresh token")),
+    };
+
+    if stored.revoked_at.is_some() {
+        return Err(AppError::authentication("Refresh token has been revoked"));
+    }
+
+    // A token that was already rotated means it leaked: kill the whole family
+    if stored.used_at.is_some() {
+        revoke_refresh_token_family_repository(&mut *tx, stored.family_id).await?;
+        tx.commit().await?;
+
+        tracing::warn!(
+            credential_id = stored.credential_id,
+            family_id = %stored.family_id,
+            "Refresh token reuse detected, token family revoked"
+        );
+        return Err(AppError::authentication("Refresh token has already been used"));
+    }
+
+    if stored.expires_at <= Utc::now() {
+        return Err(AppError::authentication("Refresh token has expired"));
+    }
+
+    mark_refresh_token_used_repository(&mut *tx, stored.id).await?;
+    let refresh_token = store_refresh_token(&mut *tx, stored.credential_id, stored.family_id, jwt).await?;
+    tx.commit().await?;
+
+    let access_token = jwt.issue_access_token(stored.credential_id, &stored.email)?;
+
+    Ok(TokenPairResponse {
+        access_token: access_token.token,
+        refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in: access_token.expires_in,
+    })
+}
+
+
+// Always mints a new session id on login and drops the one the browser came in with (fixation defence)
+pub async fn create_session_service(
+    credential_id: i32,
+    email: String,
+    previous_session_id: Option<&str>,
+    pool: &PgPool,
+    config: &SessionConfig,
+) -> AppResult<(String, SessionResponse)> {
+    let session_id = generate_token();
+    let now = Utc::now();
+    let absolute_expires_at = now + Duration::seconds(config.absolute_timeout_seconds);
+    let expires_at = (now + Duration::seconds(config.idle_timeout_seconds)).min(absolute_expires_at);
+
+    let mut tx = pool.begin().await?;
+    if let Some(previous_session_id) = previous_session_id {
+        delete_session_repository(&mut *tx, &hash_token(previous_session_id)).await?;
+    }
+    insert_session_repository(&mut *tx, credential_id, &hash_token(&session_id), expires_at, absolute_expires_at).await?;
+    tx.commit().await?;
+
+    Ok((session_id, SessionResponse {
+        id: credential_id,
+        email,
+        expires_at,
+    }))
+}
+
+
+// Ends only the caller's own session or access token (plus the refresh family it names, if any)
+pub async fn logout_service(
+    auth: &Authenticated,
+    input: Option<LogoutRequest>,
+    pool: &PgPool,
+) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+
+    match auth {
+        Authenticated::Bearer(user) => {
+            revoke_access_token_repository(&mut *tx, user.id, user.jti, user.expires_at).await?;
+        }
+        Authenticated::Session(session) => {
+            delete_session_repository(&mut *tx, &session.session_hash).await?;
+        }
+    }
+
+    if let Some(refresh_token) = input.and_then(|input| input.refresh_token) {
+        revoke_refresh_token_family_by_token_repository(&mut *tx, auth.credential_id(), &hash_token(&refresh_token)).await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+// Ends every session, refresh token family, outstanding access token and API key of the caller
+pub async fn logout_all_service(
+    credential_id: i32,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+    revoke_all_credential_access(&mut tx, credential_id, jwt).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn revoke_all_credential_access(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    jwt: &JwtKeys,
+) -> AppResult<()> {
+    revoke_other_credential_access(conn, credential_id, None, None, jwt).await
+}
+
+// The one sign-out policy for a changed password or email: every session, refresh token, access token
+// issued up to now and API key of the credential goes, except what the caller carries on with. A cookie
+// caller keeps the session `keep_session_hash`; a bearer caller keeps the access token `keep_jti` (its own,
+// or the fresh one of a new pair) but no refresh token. API keys are always revoked, the caller's own
+// included, since they would otherwise outlive the takeover the change may be ending.
+pub async fn revoke_other_credential_access(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    keep_session_hash: Option<&str>,
+    keep_jti: Option<Uuid>,
+    jwt: &JwtKeys,
+) -> AppResult<()> {
+    match keep_session_hash {
+        Some(session_hash) => delete_other_sessions_repository(&mut *conn, credential_id, session_hash).await?,
+        None => delete_sessions_for_credential_repository(&mut *conn, credential_id).await?,
+    }
+    revoke_refresh_tokens_for_credential_repository(&mut *conn, credential_id).await?;
+    revoke_access_tokens_issued_before_now_repository(&mut *conn, credential_id, jwt.access_token_ttl_seconds() as i64, keep_jti)
+        .await?;
+    revoke_api_keys_for_credential_repository(&mut *conn, credential_id).await?;
+    Ok(())
+}
+
+
+// Always answers the same way so the endpoint can't be used to probe which emails are registered.
+// In enumeration-safe mode the lookup, token write and mail all happen after the response, so its
+// latency doesn't depend on whether the account exists either
+pub async fn forgot_password_service(
+    input: ForgotPasswordRequest,
+    pool: &PgPool,
+    mailer: &Arc<dyn Mailer>,
+    config: &Arc<AuthConfig>,
+) -> AppResult<MessageResponse> {
+    validate_email(&input.email)?;
+    let normalized_email = input.email.to_lowercase().trim().to_string();
+
+    if config.enumeration_safe {
+        let (pool, mailer, config) = (pool.clone(), mailer.clone(), config.clone());
+        tokio::spawn(async move {
+            if let Err(err) = issue_password_reset(&normalized_email, &pool, &mailer, &config).await {
+                tracing::error!("Failed to issue password reset: {}", err);
+            }
+        });
+    } else {
+        issue_password_reset(&normalized_email, pool, mailer, config).await?;
+    }
+
+    Ok(MessageResponse {
+        message: "If that email is registered, a password reset link has been sent".to_string(),
+    })
+}
+
+async fn issue_password_reset(
+    normalized_email: &str,
+    pool: &PgPool,
+    mailer: &Arc<dyn Mailer>,
+    config: &AuthConfig,
+) -> AppResult<()> {
+    let Some(credentials) = get_stored_credentials_by_email_repository(normalized_email, pool).await? else {
+        return Ok(());
+    };
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(config.password_reset_token_ttl_seconds);
+
+    let mut tx = pool.begin().await?;
+    replace_password_reset_token_repository(&mut tx, credentials.id, &hash_token(&token), expires_at).await?;
+    tx.commit().await?;
+
+    let email = Email {
+        to: credentials.email,
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Use the link below to choose a new password. It expires in {} minutes.\n\n{}/reset-password?token={}\n\nIf you didn't ask for this, you can ignore this email.",
+            config.password_reset_token_ttl_seconds / 60,
+            config.app_base_url,
+            token
+        ),
+    };
+    mailer.send(email).await
+}
+
+// For flows that only mail existing accounts: in enumeration-safe mode delivery happens after the
+// response, so its time can't tell the caller whether the address is registered
+async fn deliver_account_email(mailer: &Arc<dyn Mailer>, email: Email, config: &AuthConfig) -> AppResult<()> {
+    if config.enumeration_safe {
+        send_in_background(mailer.clone(), email);
+        Ok(())
+    } else {
+        mailer.send(email).await
+    }
+}
+
+// Same evaluation registration and password changes enforce, for live feedback while typing
+// The endpoint is public, so the estimate runs on a blocking thread rather than stalling an async worker
+pub async fn password_strength_service(
+    input: PasswordStrengthRequest,
+    password_policy: &Arc<PasswordPolicy>,
+) -> AppResult<PasswordEvaluation> {
+    let password_policy = password_policy.clone();
+    tokio::task::spawn_blocking(move || password_policy.evaluate(&input.password, input.email.as_deref()))
+        .await
+        .map_err(|err| AppError::internal(format!("Password strength task failed: {err}")))
+}
+
+// Consumes the token, stores the new hash and signs the account out everywhere. The history check and the
+// new hash run before the transaction; inside it the token is consumed only if still unused and the password
+// replaced only if it is still the one that was checked, so no Argon2 work ever holds a row lock.
+pub async fn reset_password_service(
+    input: ResetPasswordRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    config: &AuthConfig,
+    passwords: &HashingPool,
+    password_policy: &PasswordPolicy,
+) -> AppResult<()> {
+    let invalid = || AppError::validation("Password reset token is invalid or has expired");
+
+    let stored = find_password_reset_token_repository(pool, &hash_token(&input.token))
+        .await?
+        .filter(|stored| stored.used_at.is_none() && stored.expires_at > Utc::now())
+        .ok_or_else(invalid)?;
+
+    let credentials = get_stored_credentials_by_id_repository(stored.credential_id, pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("Credentials"))?;
+    password_policy.check(&input.new_password, Some(&credentials.email))?;
+    ensure_password_not_reused_service(
+        pool,
+        stored.credential_id,
+        &input.new_password,
+        config.password_history_depth,
+        passwords,
+    )
+    .await?;
+    let hashed_password = passwords.hash(&input.new_password).await?;
+
+    let mut tx = pool.begin().await?;
+    if !consume_password_reset_token_repository(&mut *tx, stored.id).await? {
+        return Err(invalid());
+    }
+    update_password_repository(&mut *tx, stored.credential_id, &credentials.password_hash, &hashed_password)
+        .await?
+        .ok_or_else(password_changed_concurrently)?;
+    record_password_history_service(&mut tx, stored.credential_id, &hashed_password, config.password_history_depth).await?;
+    revoke_all_credential_access(&mut tx, stored.credential_id, jwt).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub fn password_changed_concurrently() -> AppError {
+    AppError::conflict("The password was changed by another request, try again")
+}
+
+
+const EMAIL_VERIFICATION_PURPOSE: &str = "email_verification";
+
+// Verification links are signed, stateless tokens bound to the credential id and the current address
+pub async fn send_verification_email(
+    credential_id: i32,
+    email: &str,
+    jwt: &JwtKeys,
+    mailer: &dyn Mailer,
+    config: &AuthConfig,
+) -> AppResult<()> {
+    mailer.send(verification_email(credential_id, email, jwt, config)?).await
+}
+
+pub fn verification_email(credential_id: i32, email: &str, jwt: &JwtKeys, config: &AuthConfig) -> AppResult<Email> {
+    let token = jwt.issue_purpose_token(
+        EMAIL_VERIFICATION_PURPOSE,
+        credential_id,
+        email,
+        config.email_verification_token_ttl_seconds as u64,
+    )?;
+
+    Ok(Email {
+        to: email.to_string(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "Confirm your email address by opening the link below:\n\n{}/verify-email?token={}\n\nIf you didn't create an account, you can ignore this email.",
+            config.app_base_url,
+            token
+        ),
+    })
+}
+
+pub async fn verify_email_service(
+    input: VerifyEmailRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+) -> AppResult<MessageResponse> {
+    let invalid = || AppError::validation("Verification link is invalid or has expired");
+
+    let claims = jwt
+        .decode_purpose_token(EMAIL_VERIFICATION_PURPOSE, &input.token)
+        .map_err(|_| invalid())?;
+    let credential_id = claims.credential_id().map_err(|_| invalid())?;
+
+    if !mark_email_verified_repository(pool, credential_id, &claims.email).await? {
+        return Err(invalid());
+    }
+
+    Ok(MessageResponse {
+        message: "Email address verified".to_string(),
+    })
+}
+
+// Same answer whether the address is unknown, unverified or already verified
+pub async fn resend_verification_service(
+    input: ResendVerificationRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    mailer: &Arc<dyn Mailer>,
+    config: &AuthConfig,
+) -> AppResult<MessageResponse> {
+    validate_email(&input.email)?;
+    let normalized_email = input.email.to_lowercase().trim().to_string();
+
+    let credentials = get_stored_credentials_by_email_repository(&normalized_email, pool).await?;
+    if let Some(credentials) = credentials.filter(|credentials| credentials.email_verified_at.is_none()) {
+        let email = verification_email(credentials.id, &credentials.email, jwt, config)?;
+        deliver_account_email(mailer, email, config).await?;
+    }
+
+    Ok(MessageResponse {
+        message: "If that email is registered and unverified, a verification link has been sent".to_string(),
+    })
+}
+
+
+// Unknown addresses count towards the rate limit and get the same answer, they just never receive an email
+pub async fn request_magic_link_service(
+    input: MagicLinkRequest,
+    pool: &PgPool,
+    mailer: &Arc<dyn Mailer>,
+    config: &AuthConfig,
+) -> AppResult<MessageResponse> {
+    validate_email(&input.email)?;
+    let normalized_email = input.email.to_lowercase().trim().to_string();
+    let now = Utc::now();
+
+    let mut tx = pool.begin().await?;
+    lock_magic_link_address_repository(&mut tx, &normalized_email).await?;
+
+    let window = Duration::seconds(config.magic_link_window_seconds);
+    let (recent, oldest) = recent_magic_link_requests_repository(&mut *tx, &normalized_email, now - window).await?;
+    if recent >= config.magic_link_max_requests {
+        let retry_after = oldest.map_or(config.magic_link_window_seconds, |oldest| (oldest + window - now).num_seconds());
+        return Err(AppError::rate_limited(
+            "Too many login links requested for this address, try again later",
+            retry_after.max(1) as u64,
+        ));
+    }
+
+    let credentials = get_stored_credentials_by_email_repository(&normalized_email, &mut *tx).await?;
+    let token = credentials.as_ref().map(|_| generate_token());
+    let expires_at = now + Duration::seconds(config.magic_link_token_ttl_seconds);
+
+    insert_magic_link_token_repository(
+        &mut tx,
+        &normalized_email,
+        credentials.as_ref().map(|credentials| credentials.id),
+        token.as_deref().map(hash_token).as_deref(),
+        expires_at,
+    )
+    .await?;
+    tx.commit().await?;
+
+    if let (Some(credentials), Some(token)) = (credentials, token) {
+        let email = Email {
+            to: credentials.email,
+            subject: "Your login link".to_string(),
+            body: format!(
+                "Use the link below to log in. It expires in {} minutes and works only once.\n\n{}/magic-link?token={}\n\nIf you didn't ask for this, you can ignore this email.",
+                config.magic_link_token_ttl_seconds / 60,
+                config.app_base_url,
+                token
+            ),
+        };
+        deliver_account_email(mailer, email, config).await?;
+    }
+
+    Ok(MessageResponse {
+        message: "If that email is registered, a login link has been sent".to_string(),
+    })
+}
+
+// Opening the link proves control of the mailbox, so it also verifies the address
+pub async fn consume_magic_link_service(
+    token: &str,
+    pool: &PgPool,
+) -> AppResult<ConsumedMagicLink> {
+    let invalid = || AppError::authentication("Login link is invalid or has expired");
+
+    let consumed = consume_magic_link_token_repository(pool, &hash_token(token))
+        .await?
+        .ok_or_else(invalid)?;
+
+    // Fails if the account's address changed after the link was sent
+    if !mark_email_verified_repository(pool, consumed.credential_id, &consumed.email).await? {
+        return Err(invalid());
+    }
+
+    Ok(consumed)
+}
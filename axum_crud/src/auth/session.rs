@@ -0,0 +1,103 @@
+use axum::{
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::{DateTime, Utc};
+
+use crate::auth::repository::touch_session_repository;
+use crate::auth::tokens::hash_token;
+use crate::crud::error_traits::AppError;
+use crate::grouped_routes::main_route::AppState;
+use crate::config::env::{bool_or, parse_or};
+
+const DEFAULT_IDLE_TIMEOUT_SECONDS: i64 = 30 * 60;
+const DEFAULT_ABSOLUTE_TIMEOUT_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+pub struct SessionConfig {
+    pub cookie_name: String,
+    pub same_site: SameSite,
+    pub secure: bool,
+    pub domain: Option<String>,
+    pub idle_timeout_seconds: i64,
+    pub absolute_timeout_seconds: i64,
+}
+
+impl SessionConfig {
+    pub fn from_env() -> Self {
+        let same_site = match std::env::var("SESSION_COOKIE_SAME_SITE").as_deref() {
+            Ok("Strict") => SameSite::Strict,
+            Ok("None") => SameSite::None,
+            Ok("Lax") | Err(_) => SameSite::Lax,
+            Ok(other) => panic!("Unsupported SESSION_COOKIE_SAME_SITE: {other} (expected Strict, Lax or None)"),
+        };
+
+        let secure = bool_or("SESSION_COOKIE_SECURE", true);
+
+        // Browsers drop SameSite=None cookies that aren't Secure
+        if same_site == SameSite::None && !secure {
+            panic!("SESSION_COOKIE_SAME_SITE=None requires SESSION_COOKIE_SECURE=true");
+        }
+
+        Self {
+            cookie_name: std::env::var("SESSION_COOKIE_NAME").unwrap_or_else(|_| "session_id".to_string()),
+            same_site,
+            secure,
+            domain: std::env::var("SESSION_COOKIE_DOMAIN").ok().filter(|domain| !domain.is_empty()),
+            idle_timeout_seconds: parse_or("SESSION_IDLE_TIMEOUT_SECONDS", DEFAULT_IDLE_TIMEOUT_SECONDS),
+            absolute_timeout_seconds: parse_or("SESSION_ABSOLUTE_TIMEOUT_SECONDS", DEFAULT_ABSOLUTE_TIMEOUT_SECONDS),
+        }
+    }
+
+    // Removal cookie must carry the same path/domain as the original or browsers keep it
+    pub fn removal_cookie(&self) -> Cookie<'static> {
+        let mut cookie = self.session_cookie(String::new());
+        cookie.make_removal();
+        cookie
+    }
+
+    pub fn session_cookie(&self, session_id: String) -> Cookie<'static> {
+        let mut builder = Cookie::build((self.cookie_name.clone(), session_id))
+            .path("/")
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .max_age(time::Duration::seconds(self.absolute_timeout_seconds));
+
+        if let Some(domain) = &self.domain {
+            builder = builder.domain(domain.clone());
+        }
+
+        builder.build()
+    }
+}
+
+// Add `session: Session` to a handler's arguments to require a valid session cookie.
+// Every successful extraction slides the idle expiry forward.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub credential_id: i32,
+    pub email: String,
+    pub session_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl FromRequestParts<AppState> for Session {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let session_id = jar
+            .get(&state.sessions.cookie_name)
+            .map(|cookie| cookie.value().to_string())
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| AppError::authentication("Missing session cookie"))?;
+
+        let session_hash = hash_token(&session_id);
+
+        match touch_session_repository(&state.db, &session_hash, state.sessions.idle_timeout_seconds).await? {
+            Some(session) => Ok(session),
+            None => Err(AppError::authentication("Session is invalid or has expired")),
+        }
+    }
+}
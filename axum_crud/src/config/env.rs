@@ -0,0 +1,28 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+// Shared by every `from_env()`: an unset variable falls back to the default, a set but malformed one
+// panics at startup instead of quietly running with a value nobody asked for
+pub fn parse_or<T>(name: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .unwrap_or_else(|err| panic!("{name} has an invalid value {value:?}: {err}")),
+        Err(_) => default,
+    }
+}
+
+pub fn bool_or(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} must be true or false, got {value:?}")),
+        Err(_) => default,
+    }
+}
@@ -7,7 +7,33 @@ pub struct RequestCredentials{
     pub password : String
 }
 
+// PUT replaces every writable field. Passwords aren't among them: they change through
+// /account/password or a reset, which check the current password or a mailed token.
+#[derive(Deserialize,Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReplaceCredentialsRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize,Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateCredentialsRequest {
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct ListCredentialsQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
 #[derive(serde::Deserialize)]
 pub struct GetByEmailRequest {
     pub email: String,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize,Debug)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
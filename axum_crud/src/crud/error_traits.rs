@@ -2,13 +2,15 @@
 
 use axum::{
  
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{Serialize};
 use thiserror::Error;
 
+use crate::passwords::policy::PolicyViolation;
+
 // Sophisticated Error Type using thiserror
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -24,20 +26,35 @@ pub enum AppError {
     #[error("Conflict: {message}")]
     Conflict { message: String },
     
-    // #[error("Authentication failed: {reason}")]
-    // Authentication { reason: String },
+    #[error("Authentication failed: {reason}")]
+    Authentication { reason: String },
     
-    // #[error("Authorization failed: {reason}")]
-    // Authorization { reason: String },
+    #[error("Authorization failed: {reason}")]
+    Authorization { reason: String },
     
-    #[error("Password hashing failed")]
-    PasswordHashing(#[from] bcrypt::BcryptError),
+    #[error("Password hashing failed: {0}")]
+    PasswordHashing(String),
     
     #[error("Invalid email format: {email}")]
     InvalidEmail { email: String },
     
-    // #[error("Internal server error: {message}")]
-    // Internal { message: String },
+    #[error("Internal server error: {message}")]
+    Internal { message: String },
+
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, retry_after_seconds: u64 },
+
+    #[error("Account locked")]
+    AccountLocked { retry_after_seconds: u64 },
+
+    #[error("Password was used recently")]
+    PasswordReused { depth: i64 },
+
+    #[error("Service unavailable: {message}")]
+    ServiceUnavailable { message: String, retry_after_seconds: u64 },
+
+    #[error("Password does not meet the policy")]
+    PasswordPolicy { violations: Vec<PolicyViolation> },
 }
 
 // Error Response for JSON API
@@ -72,6 +89,18 @@ impl AppError {
     }
 }
 
+impl From<bcrypt::BcryptError> for AppError {
+    fn from(err: bcrypt::BcryptError) -> Self {
+        AppError::PasswordHashing(err.to_string())
+    }
+}
+
+impl From<argon2::password_hash::Error> for AppError {
+    fn from(err: argon2::password_hash::Error) -> Self {
+        AppError::PasswordHashing(err.to_string())
+    }
+}
+
 // Convert sqlx::Error to AppError with custom logic
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
@@ -82,6 +111,14 @@ impl From<sqlx::Error> for AppError {
 // Implement IntoResponse for AppError
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        // Clients are told when they may retry
+        let retry_after = match &self {
+            AppError::RateLimited { retry_after_seconds, .. }
+            | AppError::AccountLocked { retry_after_seconds }
+            | AppError::ServiceUnavailable { retry_after_seconds, .. } => Some(*retry_after_seconds),
+            _ => None,
+        };
+
         let (status, error_code, message, details) = match self {
             AppError::Database(err) => {
                 tracing::error!("Database error: {:?}", err);
@@ -110,18 +147,18 @@ impl IntoResponse for AppError {
                 message,
                 None,
             ),
-            // AppError::Authentication { reason } => (
-            //     StatusCode::UNAUTHORIZED,
-            //     "AUTHENTICATION_FAILED".to_string(),
-            //     reason,
-            //     None,
-            // ),
-            // AppError::Authorization { reason } => (
-            //     StatusCode::FORBIDDEN,
-            //     "AUTHORIZATION_FAILED".to_string(),
-            //     reason,
-            //     None,
-            // ),
+            AppError::Authentication { reason } => (
+                StatusCode::UNAUTHORIZED,
+                "AUTHENTICATION_FAILED".to_string(),
+                reason,
+                None,
+            ),
+            AppError::Authorization { reason } => (
+                StatusCode::FORBIDDEN,
+                "AUTHORIZATION_FAILED".to_string(),
+                reason,
+                None,
+            ),
             AppError::PasswordHashing(err) => {
                 tracing::error!("Password hashing error: {:?}", err);
                 (
@@ -137,15 +174,45 @@ impl IntoResponse for AppError {
                 "Invalid email format".to_string(),
                 Some(serde_json::json!({ "email": email })),
             ),
-            // AppError::Internal { message } => {
-            //     tracing::error!("Internal error: {}", message);
-            //     (
-            //         StatusCode::INTERNAL_SERVER_ERROR,
-            //         "INTERNAL_SERVER_ERROR".to_string(),
-            //         "An internal server error occurred".to_string(),
-            //         None,
-            //     )
-            // }
+            AppError::Internal { message } => {
+                tracing::error!("Internal error: {}", message);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "INTERNAL_SERVER_ERROR".to_string(),
+                    "An internal server error occurred".to_string(),
+                    None,
+                )
+            }
+            AppError::RateLimited { message, retry_after_seconds } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "RATE_LIMITED".to_string(),
+                message,
+                Some(serde_json::json!({ "retry_after_seconds": retry_after_seconds })),
+            ),
+            AppError::AccountLocked { retry_after_seconds } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "ACCOUNT_LOCKED".to_string(),
+                "Too many failed login attempts, try again later".to_string(),
+                Some(serde_json::json!({ "retry_after_seconds": retry_after_seconds })),
+            ),
+            AppError::PasswordReused { depth } => (
+                StatusCode::BAD_REQUEST,
+                "PASSWORD_REUSED".to_string(),
+                format!("Password must not match any of your last {depth} passwords"),
+                Some(serde_json::json!({ "history_depth": depth })),
+            ),
+            AppError::ServiceUnavailable { message, retry_after_seconds } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "SERVICE_UNAVAILABLE".to_string(),
+                message,
+                Some(serde_json::json!({ "retry_after_seconds": retry_after_seconds })),
+            ),
+            AppError::PasswordPolicy { violations } => (
+                StatusCode::BAD_REQUEST,
+                "PASSWORD_POLICY_VIOLATION".to_string(),
+                "Password does not meet the password policy".to_string(),
+                Some(serde_json::json!({ "violations": violations })),
+            ),
         };
 
         let body = Json(ErrorResponse {
@@ -154,7 +221,10 @@ impl IntoResponse for AppError {
             details,
         });
 
-        (status, body).into_response()
+        match retry_after {
+            Some(seconds) => (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
     }
 }
 
@@ -172,23 +242,23 @@ impl AppError {
         }
     }
 
-    // pub fn conflict(message: impl Into<String>) -> Self {
-    //     Self::Conflict {
-    //         message: message.into(),
-    //     }
-    // }
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict {
+            message: message.into(),
+        }
+    }
 
-    // pub fn authentication(reason: impl Into<String>) -> Self {
-    //     Self::Authentication {
-    //         reason: reason.into(),
-    //     }
-    // }
+    pub fn authentication(reason: impl Into<String>) -> Self {
+        Self::Authentication {
+            reason: reason.into(),
+        }
+    }
 
-    // pub fn authorization(reason: impl Into<String>) -> Self {
-    //     Self::Authorization {
-    //         reason: reason.into(),
-    //     }
-    // }
+    pub fn authorization(reason: impl Into<String>) -> Self {
+        Self::Authorization {
+            reason: reason.into(),
+        }
+    }
 
     pub fn invalid_email(email: impl Into<String>) -> Self {
         Self::InvalidEmail {
@@ -196,11 +266,25 @@ impl AppError {
         }
     }
 
-    // pub fn internal(message: impl Into<String>) -> Self {
-    //     Self::Internal {
-    //         message: message.into(),
-    //     }
-    // }
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::Internal {
+            message: message.into(),
+        }
+    }
+
+    pub fn rate_limited(message: impl Into<String>, retry_after_seconds: u64) -> Self {
+        Self::RateLimited {
+            message: message.into(),
+            retry_after_seconds,
+        }
+    }
+
+    pub fn service_unavailable(message: impl Into<String>, retry_after_seconds: u64) -> Self {
+        Self::ServiceUnavailable {
+            message: message.into(),
+            retry_after_seconds,
+        }
+    }
 }
 
 // Custom Result type alias
@@ -1,14 +1,19 @@
 use axum::{
-    extract::{State},
+    extract::{Path, Query, State},
     Json,
-    response::IntoResponse,
-    http::StatusCode,
+    response::{IntoResponse, Response},
+    http::{header, StatusCode},
 };
-use crate::crud::services::{save_credentials_service,get_credentials_by_email_service};
+use crate::crud::services::{save_credentials_service,get_credentials_by_email_service,login_service};
+use crate::crud::services::{get_credentials_service,update_credentials_service,notify_email_change_service,delete_credentials_service,list_credentials_service};
 // use crate::crud::model::ResponseCredentials;
-use crate::crud::dto::{RequestCredentials,GetByEmailRequest};
+use crate::crud::dto::{RequestCredentials,GetByEmailRequest,LoginRequest,ReplaceCredentialsRequest,UpdateCredentialsRequest,ListCredentialsQuery};
 use crate::grouped_routes::main_route::AppState;
-use crate::crud::error_traits::{AppResult};
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::model::{CurrentUserResponse, RegistrationResponse};
+use crate::auth::extractor::AuthUser;
+use crate::rbac::extractor::{Principal, RequirePermission};
+use crate::rbac::permissions::CredentialsRead;
 
 // #[axum::debug_handler]
 // pub async fn save_credentials_handler(
@@ -45,19 +50,150 @@ pub async fn save_credentials_handler(
     State(state): State<AppState>,
     Json(body): Json<RequestCredentials>,
 ) -> AppResult<impl IntoResponse> {
-    let response = save_credentials_service(body, &state.db).await?;
-    Ok((StatusCode::CREATED, Json(response)))
+    let response = save_credentials_service(
+        body,
+        &state.db,
+        &state.jwt,
+        &state.mailer,
+        &state.auth_config,
+        &state.passwords,
+        &state.password_policy,
+    )
+    .await?;
+    let status = match response {
+        RegistrationResponse::Created(_) => StatusCode::CREATED,
+        RegistrationResponse::Accepted(_) => StatusCode::ACCEPTED,
+    };
+    Ok((status, Json(response)))
+}
+
+// POST /credentials, same as save_credentials but points at the new record
+#[axum::debug_handler]
+pub async fn create_credentials_handler(
+    State(state): State<AppState>,
+    Json(body): Json<RequestCredentials>,
+) -> AppResult<Response> {
+    let response = save_credentials_service(
+        body,
+        &state.db,
+        &state.jwt,
+        &state.mailer,
+        &state.auth_config,
+        &state.passwords,
+        &state.password_policy,
+    )
+    .await?;
+    Ok(match response {
+        RegistrationResponse::Created(ref saved) => {
+            let location = format!("/credentials/{}", saved.id);
+            (StatusCode::CREATED, [(header::LOCATION, location)], Json(response)).into_response()
+        }
+        RegistrationResponse::Accepted(_) => (StatusCode::ACCEPTED, Json(response)).into_response(),
+    })
+}
+
+// Admin listing; the same cursors are repeated in the Link header for clients that follow it
+#[axum::debug_handler]
+pub async fn list_credentials_handler(
+    State(state): State<AppState>,
+    _guard: RequirePermission<CredentialsRead>,
+    Query(query): Query<ListCredentialsQuery>,
+) -> AppResult<Response> {
+    let limit = query.limit.map(|limit| format!("limit={limit}&")).unwrap_or_default();
+    let page = list_credentials_service(query, &state.db, &state.pagination).await?;
+
+    let links: Vec<String> = [("next", &page.next_cursor), ("prev", &page.prev_cursor)]
+        .into_iter()
+        .filter_map(|(rel, cursor)| {
+            cursor.as_ref().map(|cursor| format!("</credentials?{limit}cursor={cursor}>; rel=\"{rel}\""))
+        })
+        .collect();
+
+    let mut response = (StatusCode::OK, Json(page)).into_response();
+    if !links.is_empty() {
+        let link = header::HeaderValue::from_str(&links.join(", ")).map_err(|err| AppError::internal(err.to_string()))?;
+        response.headers_mut().insert(header::LINK, link);
+    }
+    Ok(response)
+}
+
+#[axum::debug_handler]
+pub async fn get_credentials_handler(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let credentials = get_credentials_service(id, &principal, &state.db, &state.policy).await?;
+    Ok((StatusCode::OK, Json(credentials)))
+}
+
+#[axum::debug_handler]
+pub async fn replace_credentials_handler(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<i32>,
+    Json(body): Json<ReplaceCredentialsRequest>,
+) -> AppResult<impl IntoResponse> {
+    let (credentials, email_change) =
+        update_credentials_service(id, Some(body.email), &principal, &state.db, &state.policy, &state.jwt).await?;
+    if let Some(change) = email_change {
+        notify_email_change_service(&change, &state.jwt, &state.mailer, &state.auth_config);
+    }
+    Ok((StatusCode::OK, Json(credentials)))
+}
+
+#[axum::debug_handler]
+pub async fn update_credentials_handler(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateCredentialsRequest>,
+) -> AppResult<impl IntoResponse> {
+    let (credentials, email_change) =
+        update_credentials_service(id, body.email, &principal, &state.db, &state.policy, &state.jwt).await?;
+    if let Some(change) = email_change {
+        notify_email_change_service(&change, &state.jwt, &state.mailer, &state.auth_config);
+    }
+    Ok((StatusCode::OK, Json(credentials)))
+}
+
+#[axum::debug_handler]
+pub async fn delete_credentials_handler(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    delete_credentials_service(id, &principal, &state.db, &state.policy).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
 pub async fn get_credentials_by_email_json_handler(
     State(state): State<AppState>,
+    principal: Principal,
     Json(body): Json<GetByEmailRequest>,
 ) -> AppResult<impl IntoResponse> {
-    let credentials = get_credentials_by_email_service(&body.email, &state.db).await?;
+    let credentials = get_credentials_by_email_service(&body.email, &principal, &state.db, &state.policy).await?;
     Ok((StatusCode::OK, Json(credentials)))
 }
 
+#[axum::debug_handler]
+pub async fn login_handler(
+    State(state): State<AppState>,
+    Json(body): Json<LoginRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = login_service(body, &state.db, &state.jwt, &state.auth_config, &state.totp, &state.passwords).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn me_handler(auth: AuthUser) -> AppResult<impl IntoResponse> {
+    Ok((StatusCode::OK, Json(CurrentUserResponse {
+        id: auth.id,
+        email: auth.email,
+    })))
+}
+
 
 // #[axum::debug_handler]
 // pub async fn get_credentials_by_email_json_handler(
@@ -88,4 +224,194 @@ pub async fn get_credentials_by_email_json_handler(
 //             )
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{header, Method, Request, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::api_keys::extractor::API_KEY_HEADER;
+    use crate::grouped_routes::main_route::AppState;
+    use crate::test_support::{create_account, json_request, send, send_json, test_state, wait_for_mail, RecordingMailer, TestResponse};
+
+    const OLD_EMAIL: &str = "old@example.com";
+    const NEW_EMAIL: &str = "new@example.com";
+    const PASSWORD: &str = "Correct-Horse-Battery-9";
+
+    fn get(uri: &str, header_name: header::HeaderName, value: String) -> Request<Body> {
+        Request::builder().uri(uri).header(header_name, value).body(Body::empty()).unwrap()
+    }
+
+    fn patch_email(id: i64, access_token: &str, email: &str) -> Request<Body> {
+        let mut request = json_request(Method::PATCH, &format!("/credentials/{id}"), json!({ "email": email }));
+        request.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {access_token}").parse().unwrap());
+        request
+    }
+
+    #[sqlx::test]
+    async fn changing_the_email_mails_both_addresses_and_signs_out_everywhere_else(pool: PgPool) {
+        let mailer = Arc::new(RecordingMailer::default());
+        let state = test_state(pool, mailer.clone(), |config| config.allow_unverified_login = true);
+        send_json(&state, Method::POST, "/credentials", json!({ "email": OLD_EMAIL, "password": PASSWORD })).await;
+        wait_for_mail(&mailer, OLD_EMAIL).await;
+        mailer.clear();
+
+        let login = send_json(&state, Method::POST, "/crud/login", json!({ "email": OLD_EMAIL, "password": PASSWORD })).await.json();
+        let id = login["id"].as_i64().unwrap();
+        let access_token = login["access_token"].as_str().unwrap().to_string();
+        let refresh_token = login["refresh_token"].as_str().unwrap().to_string();
+        let cookie = send_json(&state, Method::POST, "/auth/session/login", json!({ "email": OLD_EMAIL, "password": PASSWORD }))
+            .await
+            .cookie();
+        let mut create_key = json_request(Method::POST, "/auth/api_keys", json!({ "name": "ci" }));
+        create_key.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {access_token}").parse().unwrap());
+        let api_key = send(&state, create_key).await.json()["key"].as_str().unwrap().to_string();
+        let current_key = || get("/auth/api_keys/current", header::HeaderName::from_static(API_KEY_HEADER), api_key.clone());
+        assert_eq!(send(&state, current_key()).await.status, StatusCode::OK);
+
+        let updated = send(&state, patch_email(id, &access_token, NEW_EMAIL)).await;
+        assert_eq!(updated.status, StatusCode::OK);
+        assert_eq!(updated.json()["email_verified"], false);
+
+        assert_eq!(wait_for_mail(&mailer, NEW_EMAIL).await[0].subject, "Verify your email address");
+        let notice = wait_for_mail(&mailer, OLD_EMAIL).await;
+        assert_eq!(notice[0].subject, "Your email address was changed");
+        assert!(notice[0].body.contains(NEW_EMAIL));
+
+        // The caller stays signed in, everything else is gone
+        let me = get("/crud/me", header::AUTHORIZATION, format!("Bearer {access_token}"));
+        assert_eq!(send(&state, me).await.status, StatusCode::OK);
+        assert_eq!(send(&state, get("/auth/session", header::COOKIE, cookie)).await.status, StatusCode::UNAUTHORIZED);
+        let refreshed = send_json(&state, Method::POST, "/auth/refresh", json!({ "refresh_token": refresh_token })).await;
+        assert_eq!(refreshed.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&state, current_key()).await.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn resubmitting_the_same_email_changes_nothing(pool: PgPool) {
+        let mailer = Arc::new(RecordingMailer::default());
+        let state = test_state(pool, mailer.clone(), |config| config.allow_unverified_login = true);
+        send_json(&state, Method::POST, "/credentials", json!({ "email": OLD_EMAIL, "password": PASSWORD })).await;
+        wait_for_mail(&mailer, OLD_EMAIL).await;
+        mailer.clear();
+
+        let login = send_json(&state, Method::POST, "/crud/login", json!({ "email": OLD_EMAIL, "password": PASSWORD })).await.json();
+        let id = login["id"].as_i64().unwrap();
+        let refresh_token = login["refresh_token"].as_str().unwrap().to_string();
+
+        let updated = send(&state, patch_email(id, login["access_token"].as_str().unwrap(), OLD_EMAIL)).await;
+        assert_eq!(updated.status, StatusCode::OK);
+
+        let refreshed = send_json(&state, Method::POST, "/auth/refresh", json!({ "refresh_token": refresh_token })).await;
+        assert_eq!(refreshed.status, StatusCode::OK);
+        assert!(mailer.sent().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn logging_in_upgrades_a_bcrypt_hash_to_argon2id(pool: PgPool) {
+        let state = test_state(pool, Arc::new(RecordingMailer::default()), |config| config.allow_unverified_login = true);
+        let id = create_account(&state.db, OLD_EMAIL).await;
+        let bcrypt_hash = bcrypt::hash(PASSWORD, 4).unwrap();
+        sqlx::query!("UPDATE credentials SET password = $1 WHERE id = $2", bcrypt_hash, id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+
+        let login = send_json(&state, Method::POST, "/crud/login", json!({ "email": OLD_EMAIL, "password": PASSWORD })).await;
+        assert_eq!(login.status, StatusCode::OK);
+
+        let stored = sqlx::query!("SELECT password, password_scheme FROM credentials WHERE id = $1", id)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert!(stored.password.starts_with("$argon2id$"), "{} was not upgraded", stored.password);
+        assert!(stored.password_scheme.is_none());
+        assert!(!state.passwords.needs_rehash(&stored.password, None));
+
+        // The upgraded hash still takes the same password
+        let again = send_json(&state, Method::POST, "/crud/login", json!({ "email": OLD_EMAIL, "password": PASSWORD })).await;
+        assert_eq!(again.status, StatusCode::OK);
+    }
+
+    // Bearer token for a fresh account holding the admin role
+    async fn admin_token(state: &AppState) -> String {
+        let id = create_account(&state.db, "admin@example.com").await;
+        sqlx::query!(
+            "INSERT INTO credential_roles (credential_id, role_id) SELECT $1, id FROM roles WHERE name = 'admin'",
+            id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        state.jwt.issue_access_token(id, "admin@example.com").unwrap().token
+    }
+
+    async fn list(state: &AppState, admin: &str, query: &str) -> TestResponse {
+        send(state, get(&format!("/credentials?{query}"), header::AUTHORIZATION, format!("Bearer {admin}"))).await
+    }
+
+    fn emails(page: &serde_json::Value) -> Vec<&str> {
+        page["data"].as_array().unwrap().iter().map(|row| row["email"].as_str().unwrap()).collect()
+    }
+
+    fn link(response: &TestResponse) -> &str {
+        response.headers.get(header::LINK).map_or("", |link| link.to_str().unwrap())
+    }
+
+    #[sqlx::test]
+    async fn listing_walks_forward_and_back_across_pages(pool: PgPool) {
+        let state = test_state(pool, Arc::new(RecordingMailer::default()), |_| {});
+        let admin = admin_token(&state).await;
+        for n in 1..=5 {
+            create_account(&state.db, &format!("user{n}@example.com")).await;
+        }
+
+        let first = list(&state, &admin, "limit=2").await;
+        assert_eq!(first.status, StatusCode::OK);
+        let first_page = first.json();
+        assert_eq!(emails(&first_page), ["admin@example.com", "user1@example.com"]);
+        assert!(first_page["prev_cursor"].is_null());
+        let next = first_page["next_cursor"].as_str().unwrap();
+        assert_eq!(link(&first), format!("</credentials?limit=2&cursor={next}>; rel=\"next\""));
+
+        let second = list(&state, &admin, &format!("limit=2&cursor={next}")).await;
+        let second_page = second.json();
+        assert_eq!(emails(&second_page), ["user2@example.com", "user3@example.com"]);
+        assert!(link(&second).contains("rel=\"next\"") && link(&second).contains("rel=\"prev\""));
+
+        let last = list(&state, &admin, &format!("limit=2&cursor={}", second_page["next_cursor"].as_str().unwrap())).await;
+        let last_page = last.json();
+        assert_eq!(emails(&last_page), ["user4@example.com", "user5@example.com"]);
+        assert!(last_page["next_cursor"].is_null());
+        let prev = last_page["prev_cursor"].as_str().unwrap();
+        assert_eq!(link(&last), format!("</credentials?limit=2&cursor={prev}>; rel=\"prev\""));
+
+        // Walking back returns the same pages, and the first one again has nothing before it
+        let back = list(&state, &admin, &format!("limit=2&cursor={prev}")).await.json();
+        assert_eq!(emails(&back), emails(&second_page));
+        assert!(back["next_cursor"].is_string());
+        let start = list(&state, &admin, &format!("limit=2&cursor={}", back["prev_cursor"].as_str().unwrap())).await;
+        let start_page = start.json();
+        assert_eq!(emails(&start_page), emails(&first_page));
+        assert!(start_page["prev_cursor"].is_null());
+        assert!(!link(&start).contains("rel=\"prev\""));
+    }
+
+    #[sqlx::test]
+    async fn listing_rejects_oversized_pages_and_forged_cursors(pool: PgPool) {
+        let state = test_state(pool, Arc::new(RecordingMailer::default()), |_| {});
+        let admin = admin_token(&state).await;
+
+        let oversized = list(&state, &admin, &format!("limit={}", state.pagination.max_page_size + 1)).await;
+        assert_eq!(oversized.status, StatusCode::BAD_REQUEST);
+        assert_eq!(list(&state, &admin, &format!("limit={}", state.pagination.max_page_size)).await.status, StatusCode::OK);
+
+        let forged = list(&state, &admin, "cursor=bjoxOjE.AAAA").await;
+        assert_eq!(forged.status, StatusCode::BAD_REQUEST);
+        assert_eq!(forged.json()["message"], "Invalid cursor");
+    }
+}
@@ -1,9 +1,74 @@
 
+use chrono::{DateTime, Utc};
 use serde::{Serialize};
 
+use crate::auth::model::MessageResponse;
+
 
 #[derive(Serialize)]
 pub struct ResponseCredentials{
+   pub id : i32,
    pub email : String,
-   pub password : String
-}
\ No newline at end of file
+   pub password : String,
+   pub email_verified : bool
+}
+
+// One row of GET /credentials
+#[derive(Serialize)]
+pub struct CredentialsSummary {
+    pub id: i32,
+    pub email: String,
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// In enumeration-safe mode registration is always `Accepted`, new address or not
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum RegistrationResponse {
+    Created(ResponseCredentials),
+    Accepted(MessageResponse),
+}
+
+// Row used for authentication only, carries the stored hash so it must never be serialized
+pub struct StoredCredentials {
+    pub id: i32,
+    pub email: String,
+    pub password_hash: String,
+    // Set for hashes imported from other systems, see passwords::legacy
+    pub password_scheme: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+}
+
+// Tagged by `status` so clients can branch on whether a second factor is still owed
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginResponse {
+    Authenticated {
+        id: i32,
+        email: String,
+        access_token: String,
+        refresh_token: String,
+        token_type: String,
+        expires_in: u64,
+    },
+    MfaRequired {
+        mfa_token: String,
+        expires_in: u64,
+        methods: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+pub struct CurrentUserResponse {
+    pub id: i32,
+    pub email: String,
+}
+
+// Produced by update_credentials_service when the address actually changed
+#[derive(Debug)]
+pub struct EmailChange {
+    pub credential_id: i32,
+    pub previous_email: String,
+    pub new_email: String,
+}
@@ -1,10 +1,12 @@
 
 
 
-use crate::crud::model::ResponseCredentials;
+use crate::crud::model::{CredentialsSummary,ResponseCredentials,StoredCredentials};
+use crate::pagination::cursor::{Cursor, Direction};
 use crate::crud::dto::RequestCredentials;
 use crate::crud::error_traits::AppResult;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
+use chrono::{DateTime, Utc};
 
 // pub async fn save_credential_repository(
 //     input: RequestCredentials, 
@@ -37,7 +39,7 @@ use sqlx::PgPool;
 
 pub async fn save_credential_repository(
     input: RequestCredentials,
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
 ) -> AppResult<ResponseCredentials> {
     let record = sqlx::query!(
         r#"
@@ -48,12 +50,14 @@ pub async fn save_credential_repository(
         input.email,
         input.password
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(ResponseCredentials {
+        id: record.id,
         email: record.email,
         password: "[REDACTED]".to_string(), // Never return actual password
+        email_verified: false,
     })
 }
 
@@ -83,14 +87,261 @@ pub async fn get_credentials_by_mail_repository(
     pool: &PgPool,
 ) -> AppResult<Option<ResponseCredentials>> {
     let record = sqlx::query!(
-        "SELECT id, email, password, created_at FROM credentials WHERE email = $1",
+        "SELECT id, email, password, created_at, email_verified_at FROM credentials WHERE email = $1",
         email
     )
     .fetch_optional(pool)
     .await?;
 
     Ok(record.map(|r| ResponseCredentials {
+        id: r.id,
         email: r.email,
         password: "[REDACTED]".to_string(),
+        email_verified: r.email_verified_at.is_some(),
+    }))
+}
+
+pub async fn get_stored_credentials_by_email_repository(
+    email: &str,
+    executor: impl PgExecutor<'_>,
+) -> AppResult<Option<StoredCredentials>> {
+    let record = sqlx::query!(
+        "SELECT id, email, password, password_scheme, email_verified_at FROM credentials WHERE email = $1",
+        email
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|r| StoredCredentials {
+        id: r.id,
+        email: r.email,
+        password_hash: r.password,
+        password_scheme: r.password_scheme,
+        email_verified_at: r.email_verified_at,
     }))
-}
\ No newline at end of file
+}
+
+
+// Also stamps password_changed_at, which is returned
+// Compare-and-set: None when the stored hash is no longer `current_hash`, i.e. the password changed
+// after the caller read it
+pub async fn update_password_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    current_hash: &str,
+    password_hash: &str,
+) -> AppResult<Option<DateTime<Utc>>> {
+    let changed_at = sqlx::query_scalar!(
+        r#"
+        UPDATE credentials SET password = $1, password_scheme = NULL, password_changed_at = NOW()
+        WHERE id = $2 AND password = $3
+        RETURNING password_changed_at AS "password_changed_at!"
+        "#,
+        password_hash,
+        id,
+        current_hash
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(changed_at)
+}
+
+
+// Upgrades a hash after a successful login; only applies if the password hasn't changed meanwhile,
+// and leaves password_changed_at alone since the password itself is the same. False when it didn't apply.
+pub async fn rehash_password_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    old_hash: &str,
+    new_hash: &str,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        "UPDATE credentials SET password = $1, password_scheme = NULL WHERE id = $2 AND password = $3",
+        new_hash,
+        id,
+        old_hash
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+// Only matches while the address is unchanged, so a link sent to an old address can't verify a new one
+pub async fn mark_email_verified_repository(
+    pool: &PgPool,
+    id: i32,
+    email: &str,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE credentials
+        SET email_verified_at = COALESCE(email_verified_at, NOW())
+        WHERE id = $1 AND email = $2
+        "#,
+        id,
+        email
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+pub async fn get_stored_credentials_by_id_repository(
+    id: i32,
+    executor: impl PgExecutor<'_>,
+) -> AppResult<Option<StoredCredentials>> {
+    let record = sqlx::query!(
+        "SELECT id, email, password, password_scheme, email_verified_at FROM credentials WHERE id = $1",
+        id
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|r| StoredCredentials {
+        id: r.id,
+        email: r.email,
+        password_hash: r.password,
+        password_scheme: r.password_scheme,
+        email_verified_at: r.email_verified_at,
+    }))
+}
+
+pub async fn get_credentials_by_id_repository(
+    id: i32,
+    pool: &PgPool,
+) -> AppResult<Option<ResponseCredentials>> {
+    let record = sqlx::query!(
+        "SELECT id, email, email_verified_at FROM credentials WHERE id = $1",
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| ResponseCredentials {
+        id: r.id,
+        email: r.email,
+        password: "[REDACTED]".to_string(),
+        email_verified: r.email_verified_at.is_some(),
+    }))
+}
+
+// A new address has to be verified again
+// Locks the row for the rest of the caller's transaction
+pub async fn get_email_for_update_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> AppResult<Option<String>> {
+    let email = sqlx::query_scalar!("SELECT email FROM credentials WHERE id = $1 FOR UPDATE", id)
+        .fetch_optional(executor)
+        .await?;
+
+    Ok(email)
+}
+
+pub async fn update_email_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    email: &str,
+) -> AppResult<ResponseCredentials> {
+    let record = sqlx::query!(
+        r#"
+        UPDATE credentials
+        SET email = $1::VARCHAR,
+            email_verified_at = CASE WHEN email = $1::VARCHAR THEN email_verified_at END
+        WHERE id = $2
+        RETURNING id, email, email_verified_at
+        "#,
+        email,
+        id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(ResponseCredentials {
+        id: record.id,
+        email: record.email,
+        password: "[REDACTED]".to_string(),
+        email_verified: record.email_verified_at.is_some(),
+    })
+}
+
+// Sessions, tokens, keys and the rest cascade with the row
+pub async fn delete_credentials_repository(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> AppResult<bool> {
+    let result = sqlx::query!("DELETE FROM credentials WHERE id = $1", id)
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Up to `limit` rows on the cursor's side of its key, always returned in (created_at, id) order.
+// Backward pages are read descending from the key and flipped, so both directions use the index.
+pub async fn list_credentials_repository(
+    cursor: Option<Cursor>,
+    limit: i64,
+    pool: &PgPool,
+) -> AppResult<Vec<CredentialsSummary>> {
+    let mut rows = match cursor {
+        None => {
+            sqlx::query_as!(
+                CredentialsSummary,
+                r#"
+                SELECT id, email, email_verified_at IS NOT NULL AS "email_verified!", created_at
+                FROM credentials
+                ORDER BY created_at, id
+                LIMIT $1
+                "#,
+                limit
+            )
+            .fetch_all(pool)
+            .await?
+        }
+        Some(Cursor { direction: Direction::Next, created_at, id }) => {
+            sqlx::query_as!(
+                CredentialsSummary,
+                r#"
+                SELECT id, email, email_verified_at IS NOT NULL AS "email_verified!", created_at
+                FROM credentials
+                WHERE (created_at, id) > ($1, $2)
+                ORDER BY created_at, id
+                LIMIT $3
+                "#,
+                created_at,
+                id,
+                limit
+            )
+            .fetch_all(pool)
+            .await?
+        }
+        Some(Cursor { direction: Direction::Prev, created_at, id }) => {
+            sqlx::query_as!(
+                CredentialsSummary,
+                r#"
+                SELECT id, email, email_verified_at IS NOT NULL AS "email_verified!", created_at
+                FROM credentials
+                WHERE (created_at, id) < ($1, $2)
+                ORDER BY created_at DESC, id DESC
+                LIMIT $3
+                "#,
+                created_at,
+                id,
+                limit
+            )
+            .fetch_all(pool)
+            .await?
+        }
+    };
+
+    if matches!(cursor, Some(Cursor { direction: Direction::Prev, .. })) {
+        rows.reverse();
+    }
+    Ok(rows)
+}
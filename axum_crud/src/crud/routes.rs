@@ -1,12 +1,43 @@
 use axum::{
-    routing::post,
+    http::{header, HeaderValue},
+    middleware::map_response,
+    response::Response,
+    routing::{get, post},
     Router
 };
-use crate::crud::handler::{save_credentials_handler,get_credentials_by_email_json_handler};
+use crate::crud::handler::{save_credentials_handler,get_credentials_by_email_json_handler,login_handler,me_handler};
+use crate::crud::handler::{
+    create_credentials_handler,list_credentials_handler,get_credentials_handler,replace_credentials_handler,
+    update_credentials_handler,delete_credentials_handler,
+};
 use crate::grouped_routes::main_route::AppState;
 
+// RFC 9745 date (2025-10-17) the RPC-style routes were superseded by /credentials
+const DEPRECATED_SINCE: &str = "@1760659200";
+
 pub fn save_credential_crud_routes() -> Router<AppState> {
     Router::new()
-      .route("/save_credentials", post(save_credentials_handler))
-      .route("/get_by_email", post(get_credentials_by_email_json_handler))
-}
\ No newline at end of file
+      .route("/save_credentials", post(save_credentials_handler).layer(map_response(deprecated)))
+      .route("/get_by_email", post(get_credentials_by_email_json_handler).layer(map_response(deprecated)))
+      .route("/login", post(login_handler))
+      .route("/me", get(me_handler))
+}
+
+pub fn credentials_routes() -> Router<AppState> {
+    Router::new()
+      .route("/", get(list_credentials_handler).post(create_credentials_handler))
+      .route(
+          "/{id}",
+          get(get_credentials_handler)
+              .put(replace_credentials_handler)
+              .patch(update_credentials_handler)
+              .delete(delete_credentials_handler),
+      )
+}
+
+async fn deprecated(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static(DEPRECATED_SINCE));
+    headers.insert(header::LINK, HeaderValue::from_static("</credentials>; rel=\"successor-version\""));
+    response
+}
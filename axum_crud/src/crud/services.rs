@@ -1,9 +1,31 @@
 
-use crate::crud::model::ResponseCredentials;
-use crate::crud::dto::RequestCredentials;
+use crate::crud::model::{CredentialsSummary,EmailChange,ResponseCredentials,LoginResponse,RegistrationResponse,StoredCredentials};
+use crate::crud::dto::{RequestCredentials,LoginRequest,ListCredentialsQuery};
+use crate::pagination::cursor::{Cursor, Direction, Page, PaginationConfig};
 use crate::crud::error_traits::{AppResult,AppError};
 use sqlx::PgPool;
-use crate::crud::repository::{save_credential_repository,get_credentials_by_mail_repository};
+use std::sync::Arc;
+use crate::auth::jwt::JwtKeys;
+use crate::auth::config::AuthConfig;
+use crate::auth::extractor::Authenticated;
+use crate::auth::model::MessageResponse;
+use crate::auth::services::{issue_token_pair_service,revoke_other_credential_access,send_verification_email,verification_email};
+use crate::mailer::{send_in_background, Email, Mailer};
+use crate::mfa::services::mfa_challenge_service;
+use crate::mfa::totp::TotpConfig;
+use crate::policy::attributes::{credentials_resource, missing_resource};
+use crate::policy::engine::PolicyEngine;
+use crate::policy::services::authorize_service;
+use crate::rbac::extractor::Principal;
+use crate::lockout::services::{clear_failed_logins_service, ensure_not_locked_service, record_failed_login_service};
+use crate::rbac::permissions::{CredentialsRead, Permission};
+use crate::crud::repository::{save_credential_repository,get_credentials_by_mail_repository,get_stored_credentials_by_email_repository,rehash_password_repository};
+use crate::crud::repository::{get_stored_credentials_by_id_repository,get_credentials_by_id_repository,get_email_for_update_repository,update_email_repository,delete_credentials_repository,list_credentials_repository};
+use crate::rbac::repository::{count_role_holders_for_update_repository,credential_roles_repository};
+use crate::rbac::services::ADMIN_ROLE;
+use crate::passwords::pool::HashingPool;
+use crate::passwords::policy::PasswordPolicy;
+use crate::passwords::services::{record_password_history_service, record_rehashed_password_service};
 
 // pub async fn save_credentials_service(
 //     input: RequestCredentials, 
@@ -15,35 +37,180 @@ use crate::crud::repository::{save_credential_repository,get_credentials_by_mail
     
 // }
 
-fn validate_password(password: &str) -> AppResult<()> {
-    if password.len() < 8 {
-        return Err(AppError::validation(
-            "Password must be at least 8 characters long"
-        ));
+pub async fn list_credentials_service(
+    input: ListCredentialsQuery,
+    pool: &PgPool,
+    pagination: &PaginationConfig,
+) -> AppResult<Page<CredentialsSummary>> {
+    let limit = pagination.page_size(input.limit)?;
+    let cursor = input.cursor.as_deref().map(|token| pagination.decode(token)).transpose()?;
+    let backward = matches!(cursor, Some(Cursor { direction: Direction::Prev, .. }));
+
+    // One extra row tells whether the list goes on in the direction of travel
+    let mut rows = list_credentials_repository(cursor, limit + 1, pool).await?;
+    let has_more = rows.len() as i64 > limit;
+    if has_more {
+        if backward {
+            rows.remove(0);
+        } else {
+            rows.pop();
+        }
     }
-    
-    if !password.chars().any(|c| c.is_ascii_uppercase()) {
-        return Err(AppError::validation(
-            "Password must contain at least one uppercase letter"
-        ));
+
+    // Whichever way we came from, there is a page on that side
+    let (more_after, more_before) = if backward { (true, has_more) } else { (has_more, cursor.is_some()) };
+    let position = |row: &CredentialsSummary, direction| {
+        pagination.encode(&Cursor {
+            direction,
+            created_at: row.created_at,
+            id: row.id,
+        })
+    };
+
+    Ok(Page {
+        next_cursor: rows.last().filter(|_| more_after).map(|row| position(row, Direction::Next)),
+        prev_cursor: rows.first().filter(|_| more_before).map(|row| position(row, Direction::Prev)),
+        data: rows,
+    })
+}
+
+const CREDENTIALS_UPDATE_ACTION: &str = "credentials:update";
+const CREDENTIALS_DELETE_ACTION: &str = "credentials:delete";
+
+// Authorizes `action` on the record; callers without access get 403 whether or not it exists
+async fn authorize_credentials(
+    id: i32,
+    action: &str,
+    principal: &Principal,
+    pool: &PgPool,
+    policy: &PolicyEngine,
+) -> AppResult<StoredCredentials> {
+    let credentials = get_stored_credentials_by_id_repository(id, pool).await?;
+
+    let resource = match &credentials {
+        Some(credentials) => credentials_resource(credentials.id, &credentials.email, credentials.email_verified_at.is_some()),
+        None => missing_resource("credentials"),
+    };
+    authorize_service(principal, action, &resource, pool, policy).await?;
+
+    credentials.ok_or_else(|| AppError::not_found("Credentials"))
+}
+
+pub async fn get_credentials_service(
+    id: i32,
+    principal: &Principal,
+    pool: &PgPool,
+    policy: &PolicyEngine,
+) -> AppResult<ResponseCredentials> {
+    authorize_credentials(id, CredentialsRead::NAME, principal, pool, policy).await?;
+
+    get_credentials_by_id_repository(id, pool)
+        .await?
+        .ok_or_else(|| AppError::not_found("Credentials"))
+}
+
+// `email: None` leaves the record as it is. A changed address is unverified again until the owner
+// confirms it through /auth/resend_verification.
+// A changed address signs the account out everywhere but the caller, API keys included, under the same
+// policy as a password change (revoke_other_credential_access); the caller hands the returned EmailChange
+// to notify_email_change_service once this has committed
+pub async fn update_credentials_service(
+    id: i32,
+    email: Option<String>,
+    principal: &Principal,
+    pool: &PgPool,
+    policy: &PolicyEngine,
+    jwt: &JwtKeys,
+) -> AppResult<(ResponseCredentials, Option<EmailChange>)> {
+    authorize_credentials(id, CREDENTIALS_UPDATE_ACTION, principal, pool, policy).await?;
+
+    let Some(email) = email else {
+        let current = get_credentials_by_id_repository(id, pool)
+            .await?
+            .ok_or_else(|| AppError::not_found("Credentials"))?;
+        return Ok((current, None));
+    };
+    validate_email(&email)?;
+    let normalized_email = email.to_lowercase().trim().to_string();
+
+    // The address is compared under the row lock, so a concurrent change can't slip between read and write
+    let mut tx = pool.begin().await?;
+    let previous_email = get_email_for_update_repository(&mut *tx, id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Credentials"))?;
+    let updated = update_email_repository(&mut *tx, id, &normalized_email).await?;
+    if normalized_email == previous_email {
+        tx.commit().await?;
+        return Ok((updated, None));
     }
-    
-    if !password.chars().any(|c| c.is_ascii_lowercase()) {
-        return Err(AppError::validation(
-            "Password must contain at least one lowercase letter"
-        ));
+    // Only a caller changing their own account keeps what they are signed in with
+    let (keep_session_hash, keep_jti) = match principal {
+        Principal::User(auth) if auth.credential_id() == id => match auth {
+            Authenticated::Session(session) => (Some(session.session_hash.as_str()), None),
+            Authenticated::Bearer(user) => (None, Some(user.jti)),
+        },
+        _ => (None, None),
+    };
+    revoke_other_credential_access(&mut tx, id, keep_session_hash, keep_jti, jwt).await?;
+    tx.commit().await?;
+    tracing::info!(actor_id = principal.credential_id(), credential_id = id, "Email address changed");
+
+    let change = EmailChange {
+        credential_id: id,
+        previous_email,
+        new_email: normalized_email,
+    };
+    Ok((updated, Some(change)))
+}
+
+// The new address has to be confirmed before it counts as verified, and the old one is told in case the
+// change wasn't the owner's doing. The change has already committed, so a failed send is only logged
+pub fn notify_email_change_service(change: &EmailChange, jwt: &JwtKeys, mailer: &Arc<dyn Mailer>, config: &AuthConfig) {
+    match verification_email(change.credential_id, &change.new_email, jwt, config) {
+        Ok(email) => send_in_background(mailer.clone(), email),
+        Err(err) => tracing::error!(credential_id = change.credential_id, "Failed to prepare verification email: {}", err),
     }
-    
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        return Err(AppError::validation(
-            "Password must contain at least one digit"
-        ));
+    send_in_background(mailer.clone(), email_changed_email(change));
+}
+
+fn email_changed_email(change: &EmailChange) -> Email {
+    Email {
+        to: change.previous_email.clone(),
+        subject: "Your email address was changed".to_string(),
+        body: format!(
+            "The email address on your account was changed to {}.\n\nIf you didn't make this change, contact support right away: password reset links now go to the new address.",
+            change.new_email
+        ),
     }
-    
+}
+
+pub async fn delete_credentials_service(
+    id: i32,
+    principal: &Principal,
+    pool: &PgPool,
+    policy: &PolicyEngine,
+) -> AppResult<()> {
+    authorize_credentials(id, CREDENTIALS_DELETE_ACTION, principal, pool, policy).await?;
+
+    let mut tx = pool.begin().await?;
+    // Same guard as revoking the role, deleting the account would otherwise get around it
+    if count_role_holders_for_update_repository(&mut tx, ADMIN_ROLE).await? <= 1 {
+        let roles = credential_roles_repository(&mut *tx, id).await?;
+        if roles.iter().any(|held| held == ADMIN_ROLE) {
+            return Err(AppError::conflict("Cannot delete the last admin"));
+        }
+    }
+
+    if !delete_credentials_repository(&mut *tx, id).await? {
+        return Err(AppError::not_found("Credentials"));
+    }
+    tx.commit().await?;
+    tracing::info!(actor_id = principal.credential_id(), credential_id = id, "Credentials deleted");
+
     Ok(())
 }
 
-fn validate_email(email: &str) -> AppResult<()> {
+pub fn validate_email(email: &str) -> AppResult<()> {
     if email.is_empty() {
         return Err(AppError::validation("Email cannot be empty"));
     }
@@ -65,22 +232,69 @@ fn validate_email(email: &str) -> AppResult<()> {
 pub async fn save_credentials_service(
     input: RequestCredentials,
     pool: &PgPool,
-) -> AppResult<ResponseCredentials> {
+    jwt: &JwtKeys,
+    mailer: &Arc<dyn Mailer>,
+    config: &AuthConfig,
+    passwords: &HashingPool,
+    password_policy: &PasswordPolicy,
+) -> AppResult<RegistrationResponse> {
     // Validate input
     validate_email(&input.email)?;
-    validate_password(&input.password)?;
+    password_policy.check(&input.password, Some(&input.email))?;
 
     // Hash password
-    use bcrypt::{hash, DEFAULT_COST};
-    let hashed_password = hash(&input.password, DEFAULT_COST)?;
+    let hashed_password = passwords.hash(&input.password).await?;
 
     let hashed_input = RequestCredentials {
         email: input.email.to_lowercase().trim().to_string(), // Normalize email
-        password: hashed_password,
+        password: hashed_password.clone(),
+    };
+
+    // Save to database, starting the account's password history
+    let mut tx = pool.begin().await?;
+    let saved = match save_credential_repository(hashed_input, &mut *tx).await {
+        // The owner hears about the attempt by email instead of the caller learning the address is taken;
+        // the password was hashed above either way so both paths cost the same
+        Err(AppError::Conflict { .. }) if config.enumeration_safe => {
+            send_in_background(mailer.clone(), account_exists_email(&input.email, config));
+            return Ok(RegistrationResponse::Accepted(registration_accepted()));
+        }
+        result => result?,
     };
+    record_password_history_service(&mut tx, saved.id, &hashed_password, config.password_history_depth).await?;
+    tx.commit().await?;
 
-    // Save to database
-    save_credential_repository(hashed_input, pool).await
+    if config.enumeration_safe {
+        match verification_email(saved.id, &saved.email, jwt, config) {
+            Ok(email) => send_in_background(mailer.clone(), email),
+            Err(err) => tracing::error!(credential_id = saved.id, "Failed to prepare verification email: {}", err),
+        }
+        return Ok(RegistrationResponse::Accepted(registration_accepted()));
+    }
+
+    // The account exists either way; a failed send can be retried through /auth/resend_verification
+    if let Err(err) = send_verification_email(saved.id, &saved.email, jwt, mailer.as_ref(), config).await {
+        tracing::error!(credential_id = saved.id, "Failed to send verification email: {}", err);
+    }
+
+    Ok(RegistrationResponse::Created(saved))
+}
+
+fn registration_accepted() -> MessageResponse {
+    MessageResponse {
+        message: "Check your email to finish creating your account".to_string(),
+    }
+}
+
+fn account_exists_email(email: &str, config: &AuthConfig) -> Email {
+    Email {
+        to: email.to_lowercase().trim().to_string(),
+        subject: "Someone tried to sign up with your email".to_string(),
+        body: format!(
+            "An account already exists for this address. If that was you, sign in or reset your password:\n\n{}/forgot-password\n\nIf it wasn't, you can ignore this email.",
+            config.app_base_url
+        ),
+    }
 }
 
 
@@ -94,16 +308,121 @@ pub async fn save_credentials_service(
     
 // }
 
+// Policy-checked before the existence check, so only callers allowed to read any account can tell missing from forbidden
 pub async fn get_credentials_by_email_service(
     email: &str,
+    principal: &Principal,
     pool: &PgPool,
+    policy: &PolicyEngine,
 ) -> AppResult<ResponseCredentials> {
     validate_email(email)?;
     
     let normalized_email = email.to_lowercase().trim().to_string();
-    
-     match get_credentials_by_mail_repository(&normalized_email, pool).await? {
-        Some(credentials) => Ok(credentials),
-        None => Err(AppError::not_found("User")),
+    let credentials = get_credentials_by_mail_repository(&normalized_email, pool).await?;
+
+    let resource = match &credentials {
+        Some(credentials) => credentials_resource(credentials.id, &credentials.email, credentials.email_verified),
+        None => missing_resource("credentials"),
+    };
+    authorize_service(principal, CredentialsRead::NAME, &resource, pool, policy).await?;
+
+    credentials.ok_or_else(|| AppError::not_found("User"))
+}
+
+
+
+// Shared by bearer-token and cookie-session login
+pub async fn authenticate_credentials_service(
+    input: LoginRequest,
+    pool: &PgPool,
+    config: &AuthConfig,
+    passwords: &HashingPool,
+) -> AppResult<StoredCredentials> {
+    validate_email(&input.email)?;
+
+    let normalized_email = input.email.to_lowercase().trim().to_string();
+
+    ensure_not_locked_service(&normalized_email, pool).await?;
+
+    // Same message, and the same failure accounting, for unknown email and wrong password
+    let credentials = get_stored_credentials_by_email_repository(&normalized_email, pool).await?;
+    let verified = match &credentials {
+        Some(credentials) => passwords.verify(&input.password, &credentials.password_hash, credentials.password_scheme.as_deref()).await?,
+        None => {
+            if config.enumeration_safe {
+                passwords.verify_dummy(&input.password).await?;
+            }
+            false
+        }
+    };
+    let Some(credentials) = credentials.filter(|_| verified) else {
+        record_failed_login_service(&normalized_email, pool, config).await?;
+        return Err(AppError::authentication("Invalid email or password"));
+    };
+    clear_failed_logins_service(&normalized_email, pool).await?;
+
+    // Move hashes made with an older algorithm or cost to the current one while we have the plaintext
+    if passwords.needs_rehash(&credentials.password_hash, credentials.password_scheme.as_deref()) {
+        match passwords.hash(&input.password).await {
+            Ok(new_hash) => {
+                let mut tx = pool.begin().await?;
+                if rehash_password_repository(&mut *tx, credentials.id, &credentials.password_hash, &new_hash).await? {
+                    record_rehashed_password_service(
+                        &mut tx,
+                        credentials.id,
+                        &credentials.password_hash,
+                        &new_hash,
+                        config.password_history_depth,
+                    )
+                    .await?;
+                }
+                tx.commit().await?;
+            }
+            Err(err) => tracing::warn!("Failed to rehash password for credential {}: {}", credentials.id, err),
+        }
     }
+
+    // Checked after the password so an unverified address is only revealed to its owner
+    ensure_login_verified(&credentials, config)?;
+
+    Ok(credentials)
+}
+
+// Every first factor that completes a login on its own (password, passkey) applies this once it has
+// proven the caller owns the account
+pub fn ensure_login_verified(credentials: &StoredCredentials, config: &AuthConfig) -> AppResult<()> {
+    if !config.allow_unverified_login && credentials.email_verified_at.is_none() {
+        return Err(AppError::authentication("Email address has not been verified"));
+    }
+    Ok(())
+}
+
+pub async fn login_service(
+    input: LoginRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    config: &AuthConfig,
+    totp: &TotpConfig,
+    passwords: &HashingPool,
+) -> AppResult<LoginResponse> {
+    let credentials = authenticate_credentials_service(input, pool, config, passwords).await?;
+
+    if let Some(challenge) = mfa_challenge_service(credentials.id, &credentials.email, pool, jwt, totp).await? {
+        return Ok(LoginResponse::MfaRequired {
+            mfa_token: challenge.mfa_token,
+            expires_in: challenge.expires_in,
+            methods: challenge.methods,
+        });
+    }
+
+    let tokens = issue_token_pair_service(credentials.id, &credentials.email, pool, jwt).await?;
+
+    Ok(LoginResponse::Authenticated {
+        id: credentials.id,
+        email: credentials.email,
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        token_type: tokens.token_type,
+        expires_in: tokens.expires_in,
+    })
 }
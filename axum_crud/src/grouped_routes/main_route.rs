@@ -2,17 +2,78 @@ use axum::{
      Router
 };
 
-use crate::crud::routes::save_credential_crud_routes;
+use crate::crud::routes::{save_credential_crud_routes, credentials_routes};
+use crate::auth::routes::auth_routes;
 use tower_http::cors::{CorsLayer};
 use axum::http::{Method, HeaderValue,header};
 
 use sqlx::{PgPool};
+use std::sync::Arc;
+use crate::auth::jwt::JwtKeys;
+use crate::auth::session::SessionConfig;
+use crate::auth::config::AuthConfig;
+use crate::mailer::{mailer_from_env, Mailer};
+use crate::mfa::totp::TotpConfig;
+use crate::mfa::routes::mfa_routes;
+use crate::webauthn::routes::webauthn_routes;
+use crate::api_keys::routes::api_keys_routes;
+use crate::api_keys::extractor::API_KEY_HEADER;
+use crate::rbac::routes::rbac_routes;
+use crate::policy::routes::policy_routes;
+use crate::policy::engine::PolicyEngine;
+use crate::lockout::routes::lockout_routes;
+use crate::account::routes::account_routes;
+use crate::passwords::pool::HashingPool;
+use crate::passwords::policy::PasswordPolicy;
+use crate::pagination::cursor::PaginationConfig;
+use crate::imports::routes::imports_routes;
+use crate::passwords::routes::metrics_routes;
+use crate::webauthn::decoy::PasskeyDecoys;
+use crate::webauthn::config::webauthn_from_env;
+use crate::passwords::hashing::PasswordHasher;
+use webauthn_rs::Webauthn;
+
 #[derive(Clone)]
 pub struct AppState {
    pub db: PgPool,
+   pub jwt: Arc<JwtKeys>,
+   pub sessions: Arc<SessionConfig>,
+   pub auth_config: Arc<AuthConfig>,
+   pub mailer: Arc<dyn Mailer>,
+   pub totp: Arc<TotpConfig>,
+   pub webauthn: Arc<Webauthn>,
+   pub passkey_decoys: Arc<PasskeyDecoys>,
+   pub policy: Arc<PolicyEngine>,
+   pub passwords: Arc<HashingPool>,
+   pub password_policy: Arc<PasswordPolicy>,
+   pub pagination: Arc<PaginationConfig>,
+}
+impl AppState {
+    // Every config is read from the environment once, at startup
+    pub fn from_env(db: PgPool) -> Self {
+        Self {
+            db,
+            jwt: Arc::new(JwtKeys::from_env()),
+            sessions: Arc::new(SessionConfig::from_env()),
+            auth_config: Arc::new(AuthConfig::from_env()),
+            mailer: mailer_from_env(),
+            totp: Arc::new(TotpConfig::from_env()),
+            webauthn: Arc::new(webauthn_from_env()),
+            passkey_decoys: Arc::new(PasskeyDecoys::from_env()),
+            policy: Arc::new(PolicyEngine::from_env()),
+            passwords: Arc::new(HashingPool::from_env(PasswordHasher::from_env())),
+            password_policy: Arc::new(PasswordPolicy::from_env()),
+            pagination: Arc::new(PaginationConfig::from_env()),
+        }
+    }
 }
+
 pub fn main_route(state: AppState) -> Router {
     let crud_router = save_credential_crud_routes();
+    let auth_router = auth_routes()
+        .nest("/mfa", mfa_routes())
+        .nest("/webauthn", webauthn_routes())
+        .nest("/api_keys", api_keys_routes());
 
     let cors = CorsLayer::new()
         .allow_origin("http://localhost:5173".parse::<HeaderValue>().unwrap())
@@ -22,6 +83,7 @@ pub fn main_route(state: AppState) -> Router {
             Method::GET, 
             Method::POST, 
             Method::PUT, 
+            Method::PATCH,
             Method::DELETE, 
             Method::OPTIONS
         ])
@@ -29,11 +91,25 @@ pub fn main_route(state: AppState) -> Router {
             header::AUTHORIZATION,     // For Bearer tokens
             header::CONTENT_TYPE,     // For JSON requests
             header::ACCEPT,           // Standard accept header
+            header::HeaderName::from_static(API_KEY_HEADER), // For service-account API keys
+        ])
+        .expose_headers([
+            header::LOCATION,
+            header::LINK,
+            header::HeaderName::from_static("deprecation"),
         ])
         .allow_credentials(true);
 
     let api_routes = Router::new()
         .nest("/crud", crud_router)
+        .nest("/credentials", credentials_routes())
+        .nest("/auth", auth_router)
+        .nest("/account", account_routes())
+        .nest("/rbac", rbac_routes())
+        .nest("/policy", policy_routes())
+        .nest("/admin/lockouts", lockout_routes())
+        .nest("/admin/imports", imports_routes())
+        .nest("/admin/metrics", metrics_routes())
         .layer(cors)
         .with_state(state); // Apply state here
     api_routes
@@ -0,0 +1,17 @@
+use serde::{Deserialize};
+
+#[derive(Deserialize,Debug)]
+pub struct ImportCredentialsRequest {
+    pub users: Vec<ImportedUser>,
+}
+
+// `scheme` names the format of `password_hash`, see passwords::legacy::LegacyScheme
+#[derive(Deserialize,Debug)]
+pub struct ImportedUser {
+    pub email: String,
+    pub password_hash: String,
+    pub scheme: String,
+    // Addresses the legacy system had already confirmed
+    #[serde(default)]
+    pub email_verified: bool,
+}
@@ -0,0 +1,22 @@
+use axum::{
+    extract::{State},
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::imports::dto::ImportCredentialsRequest;
+use crate::imports::services::import_credentials_service;
+use crate::rbac::extractor::RequirePermission;
+use crate::rbac::permissions::CredentialsImport;
+
+#[axum::debug_handler]
+pub async fn import_credentials_handler(
+    State(state): State<AppState>,
+    guard: RequirePermission<CredentialsImport>,
+    Json(body): Json<ImportCredentialsRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = import_credentials_service(guard.principal.credential_id(), body, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
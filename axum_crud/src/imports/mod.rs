@@ -0,0 +1,6 @@
+pub mod dto;
+pub mod model;
+pub mod repository;
+pub mod services;
+pub mod handler;
+pub mod routes;
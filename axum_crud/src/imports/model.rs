@@ -0,0 +1,27 @@
+use serde::{Serialize};
+
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Imported,
+    // An account with that email already exists and was left untouched
+    Skipped,
+    Rejected,
+}
+
+#[derive(Serialize)]
+pub struct ImportRowResult {
+    pub email: String,
+    pub status: ImportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportCredentialsResponse {
+    pub imported: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+    pub results: Vec<ImportRowResult>,
+}
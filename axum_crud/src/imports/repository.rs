@@ -0,0 +1,34 @@
+use sqlx::PgExecutor;
+
+use crate::crud::error_traits::AppResult;
+
+
+// False when the email is already taken. The imported hash also starts the account's password history.
+pub async fn insert_imported_credential_repository(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+    password_hash: &str,
+    password_scheme: &str,
+    email_verified: bool,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        WITH inserted AS (
+            INSERT INTO credentials (email, password, password_scheme, email_verified_at)
+            VALUES ($1, $2, $3, CASE WHEN $4 THEN NOW() END)
+            ON CONFLICT (email) DO NOTHING
+            RETURNING id, password, password_scheme
+        )
+        INSERT INTO password_history (credential_id, password_hash, password_scheme)
+        SELECT id, password, password_scheme FROM inserted
+        "#,
+        email,
+        password_hash,
+        password_scheme,
+        email_verified
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
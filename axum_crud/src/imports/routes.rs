@@ -0,0 +1,11 @@
+use axum::{
+    routing::post,
+    Router
+};
+use crate::imports::handler::import_credentials_handler;
+use crate::grouped_routes::main_route::AppState;
+
+pub fn imports_routes() -> Router<AppState> {
+    Router::new()
+      .route("/credentials", post(import_credentials_handler))
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use sqlx::PgPool;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::services::validate_email;
+use crate::imports::dto::{ImportCredentialsRequest, ImportedUser};
+use crate::imports::model::{ImportCredentialsResponse, ImportRowResult, ImportStatus};
+use crate::imports::repository::insert_imported_credential_repository;
+use crate::passwords::legacy::LegacyScheme;
+
+const MAX_IMPORT_BATCH: usize = 1000;
+
+
+// Each row is checked on its own; a bad row is reported and the rest of the batch still goes in
+pub async fn import_credentials_service(
+    actor_id: i32,
+    input: ImportCredentialsRequest,
+    pool: &PgPool,
+) -> AppResult<ImportCredentialsResponse> {
+    if input.users.len() > MAX_IMPORT_BATCH {
+        return Err(AppError::validation(format!("At most {MAX_IMPORT_BATCH} users can be imported at once")));
+    }
+
+    let mut seen = HashSet::new();
+    let mut results = Vec::with_capacity(input.users.len());
+
+    let mut tx = pool.begin().await?;
+    for user in input.users {
+        let email = user.email.to_lowercase().trim().to_string();
+
+        let status = match validate_row(&user, &email) {
+            Err(AppError::Validation { message }) => Err(message),
+            Err(err) => Err(err.to_string()),
+            Ok(_) if !seen.insert(email.clone()) => Err("Duplicate email in this batch".to_string()),
+            Ok(scheme) => {
+                let inserted = insert_imported_credential_repository(
+                    &mut *tx,
+                    &email,
+                    &user.password_hash,
+                    scheme.as_str(),
+                    user.email_verified,
+                )
+                .await?;
+                Ok(if inserted { ImportStatus::Imported } else { ImportStatus::Skipped })
+            }
+        };
+
+        results.push(match status {
+            Ok(status) => ImportRowResult { email, status, reason: None },
+            Err(reason) => ImportRowResult { email, status: ImportStatus::Rejected, reason: Some(reason) },
+        });
+    }
+    tx.commit().await?;
+
+    let count = |status| results.iter().filter(|row| row.status == status).count();
+    let response = ImportCredentialsResponse {
+        imported: count(ImportStatus::Imported),
+        skipped: count(ImportStatus::Skipped),
+        rejected: count(ImportStatus::Rejected),
+        results,
+    };
+    tracing::info!(
+        actor_id,
+        imported = response.imported,
+        skipped = response.skipped,
+        rejected = response.rejected,
+        "Imported credentials"
+    );
+
+    Ok(response)
+}
+
+fn validate_row(user: &ImportedUser, email: &str) -> AppResult<LegacyScheme> {
+    validate_email(email)?;
+    let scheme: LegacyScheme = user.scheme.parse()?;
+    scheme.validate(&user.password_hash)?;
+    Ok(scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{create_account, send_json, test_state, RecordingMailer};
+
+    // Django's pbkdf2_sha256 for the password "password"
+    const DJANGO_HASH: &str = "pbkdf2_sha256$1000$seasalt$YIWkt6M1JFXrHg5s0jZjBSc7C2Cz6QvchSJ0h8Y+i7c=";
+
+    fn user(email: &str, password_hash: &str, scheme: &str) -> ImportedUser {
+        ImportedUser {
+            email: email.to_string(),
+            password_hash: password_hash.to_string(),
+            scheme: scheme.to_string(),
+            email_verified: true,
+        }
+    }
+
+    async fn import(pool: &PgPool, users: Vec<ImportedUser>) -> ImportCredentialsResponse {
+        import_credentials_service(0, ImportCredentialsRequest { users }, pool).await.unwrap()
+    }
+
+    fn statuses(response: &ImportCredentialsResponse) -> Vec<(&str, ImportStatus)> {
+        response.results.iter().map(|row| (row.email.as_str(), row.status)).collect()
+    }
+
+    async fn stored(pool: &PgPool, email: &str) -> Option<(String, Option<String>)> {
+        sqlx::query!("SELECT password, password_scheme FROM credentials WHERE email = $1", email)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+            .map(|r| (r.password, r.password_scheme))
+    }
+
+    #[sqlx::test]
+    async fn a_bad_row_is_rejected_and_the_rest_of_the_batch_goes_in(pool: PgPool) {
+        let response = import(
+            &pool,
+            vec![
+                user("good@example.com", DJANGO_HASH, "django"),
+                user("not-an-email", DJANGO_HASH, "django"),
+                user("unknown@example.com", DJANGO_HASH, "md5"),
+                user("malformed@example.com", "pbkdf2_sha256$1000$seasalt$", "django"),
+                user("mismatched@example.com", DJANGO_HASH, "pbkdf2_sha256"),
+            ],
+        )
+        .await;
+
+        assert_eq!((response.imported, response.skipped, response.rejected), (1, 0, 4));
+        assert_eq!(response.results[0].status, ImportStatus::Imported);
+        assert!(response.results[0].reason.is_none());
+        assert_eq!(response.results[2].reason.as_deref(), Some("Unsupported password scheme: md5"));
+        assert_eq!(response.results[3].reason.as_deref(), Some("Malformed django hash"));
+        assert!(response.results[1..].iter().all(|row| row.status == ImportStatus::Rejected && row.reason.is_some()));
+
+        assert_eq!(stored(&pool, "good@example.com").await, Some((DJANGO_HASH.to_string(), Some("django".to_string()))));
+        assert_eq!(stored(&pool, "mismatched@example.com").await, None);
+    }
+
+    #[sqlx::test]
+    async fn only_the_first_of_duplicate_emails_in_a_batch_goes_in(pool: PgPool) {
+        let response = import(
+            &pool,
+            vec![
+                user("twice@example.com", DJANGO_HASH, "django"),
+                user(" Twice@Example.com ", "sha1$pepper$73614fd51a90257f32acee922225eb8a815b89c8", "salted_sha1"),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            statuses(&response),
+            [("twice@example.com", ImportStatus::Imported), ("twice@example.com", ImportStatus::Rejected)]
+        );
+        assert_eq!(response.results[1].reason.as_deref(), Some("Duplicate email in this batch"));
+        assert_eq!(stored(&pool, "twice@example.com").await.unwrap().1.as_deref(), Some("django"));
+    }
+
+    #[sqlx::test]
+    async fn an_existing_account_is_skipped_and_left_untouched(pool: PgPool) {
+        create_account(&pool, "taken@example.com").await;
+
+        let response = import(&pool, vec![user("taken@example.com", DJANGO_HASH, "django")]).await;
+
+        assert_eq!(statuses(&response), [("taken@example.com", ImportStatus::Skipped)]);
+        assert_eq!((response.imported, response.skipped, response.rejected), (0, 1, 0));
+        assert_eq!(stored(&pool, "taken@example.com").await, Some(("not-a-real-hash".to_string(), None)));
+    }
+
+    #[sqlx::test]
+    async fn the_first_login_upgrades_an_imported_hash(pool: PgPool) {
+        let state = test_state(pool, Arc::new(RecordingMailer::default()), |_| {});
+        import(&state.db, vec![user("imported@example.com", DJANGO_HASH, "django")]).await;
+        let credentials = json!({ "email": "imported@example.com", "password": "password" });
+
+        let login = send_json(&state, Method::POST, "/crud/login", credentials.clone()).await;
+        assert_eq!(login.status, StatusCode::OK);
+
+        let (password, scheme) = stored(&state.db, "imported@example.com").await.unwrap();
+        assert!(password.starts_with("$argon2id$"), "{password} was not upgraded");
+        assert_eq!(scheme, None);
+        assert_eq!(send_json(&state, Method::POST, "/crud/login", credentials).await.status, StatusCode::OK);
+    }
+}
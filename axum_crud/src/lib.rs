@@ -1,16 +1,35 @@
 mod grouped_routes;
 mod crud;
 mod database;
+mod auth;
+mod mailer;
+mod mfa;
+mod webauthn;
+mod api_keys;
+mod rbac;
+mod policy;
+mod lockout;
+mod account;
+mod passwords;
+mod imports;
+mod pagination;
+mod config;
+#[cfg(test)]
+mod test_support;
 
 use crate::grouped_routes::main_route::{main_route,AppState};
 use crate::database::dbconnect::connect;
+use crate::auth::cleanup::spawn_pruning_task;
+use crate::rbac::services::bootstrap_admin_service;
 
 
 pub async fn run(){
     tracing_subscriber::fmt::init();
     dotenvy::dotenv().ok();
     let pool = connect().await.unwrap();
-     let app_state = AppState { db: pool };
+    spawn_pruning_task(pool.clone());
+    bootstrap_admin_service(&pool).await.unwrap();
+     let app_state = AppState::from_env(pool);
     
     let app=main_route(app_state);
     // run our app with hyper, listening globally on port 3000
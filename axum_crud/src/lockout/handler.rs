@@ -0,0 +1,40 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::lockout::services::{list_lockouts_service,lockout_status_service,unlock_service};
+use crate::rbac::extractor::RequirePermission;
+use crate::rbac::permissions::LockoutsManage;
+
+#[axum::debug_handler]
+pub async fn list_lockouts_handler(
+    State(state): State<AppState>,
+    _guard: RequirePermission<LockoutsManage>,
+) -> AppResult<impl IntoResponse> {
+    let response = list_lockouts_service(&state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn lockout_status_handler(
+    State(state): State<AppState>,
+    _guard: RequirePermission<LockoutsManage>,
+    Path(email): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let response = lockout_status_service(&email, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn unlock_handler(
+    State(state): State<AppState>,
+    guard: RequirePermission<LockoutsManage>,
+    Path(email): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    unlock_service(guard.principal.credential_id(), &email, &state.db).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
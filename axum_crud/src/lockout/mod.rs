@@ -0,0 +1,5 @@
+pub mod model;
+pub mod repository;
+pub mod services;
+pub mod handler;
+pub mod routes;
@@ -0,0 +1,11 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize};
+
+
+#[derive(Serialize)]
+pub struct LockoutStatusResponse {
+    pub email: String,
+    pub failed_count: i32,
+    pub last_failed_at: Option<DateTime<Utc>>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
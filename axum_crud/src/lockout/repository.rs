@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+use crate::crud::error_traits::AppResult;
+use crate::lockout::model::LockoutStatusResponse;
+
+
+// Only returns a lock that is still in force
+pub async fn active_lock_repository(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> AppResult<Option<DateTime<Utc>>> {
+    let locked_until = sqlx::query_scalar!(
+        "SELECT locked_until FROM login_attempts WHERE email = $1 AND locked_until > NOW()",
+        email
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(locked_until.flatten())
+}
+
+// Failures more than a day apart don't accumulate; returns the new consecutive failure count
+pub async fn record_failed_login_repository(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> AppResult<i32> {
+    let failed_count = sqlx::query_scalar!(
+        r#"
+        INSERT INTO login_attempts (email, failed_count, last_failed_at)
+        VALUES ($1, 1, NOW())
+        ON CONFLICT (email) DO UPDATE SET
+            failed_count = CASE
+                WHEN login_attempts.last_failed_at < NOW() - INTERVAL '1 day' THEN 1
+                ELSE login_attempts.failed_count + 1
+            END,
+            last_failed_at = NOW()
+        RETURNING failed_count
+        "#,
+        email
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(failed_count)
+}
+
+pub async fn lock_login_repository(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+    locked_until: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE login_attempts SET locked_until = $2 WHERE email = $1",
+        email,
+        locked_until
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn clear_failed_logins_repository(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> AppResult<bool> {
+    let result = sqlx::query!("DELETE FROM login_attempts WHERE email = $1", email)
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn lockout_status_repository(
+    pool: &PgPool,
+    email: &str,
+) -> AppResult<Option<LockoutStatusResponse>> {
+    let record = sqlx::query!(
+        "SELECT email, failed_count, last_failed_at, locked_until FROM login_attempts WHERE email = $1",
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| LockoutStatusResponse {
+        email: r.email,
+        failed_count: r.failed_count,
+        last_failed_at: Some(r.last_failed_at),
+        locked_until: r.locked_until.filter(|locked_until| *locked_until > Utc::now()),
+    }))
+}
+
+pub async fn list_active_locks_repository(
+    pool: &PgPool,
+) -> AppResult<Vec<LockoutStatusResponse>> {
+    let records = sqlx::query!(
+        r#"
+        SELECT email, failed_count, last_failed_at, locked_until
+        FROM login_attempts
+        WHERE locked_until > NOW()
+        ORDER BY locked_until DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| LockoutStatusResponse {
+            email: r.email,
+            failed_count: r.failed_count,
+            last_failed_at: Some(r.last_failed_at),
+            locked_until: r.locked_until,
+        })
+        .collect())
+}
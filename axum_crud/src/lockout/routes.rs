@@ -0,0 +1,12 @@
+use axum::{
+    routing::get,
+    Router
+};
+use crate::lockout::handler::{list_lockouts_handler,lockout_status_handler,unlock_handler};
+use crate::grouped_routes::main_route::AppState;
+
+pub fn lockout_routes() -> Router<AppState> {
+    Router::new()
+      .route("/", get(list_lockouts_handler))
+      .route("/{email}", get(lockout_status_handler).delete(unlock_handler))
+}
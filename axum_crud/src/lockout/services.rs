@@ -0,0 +1,224 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::auth::config::AuthConfig;
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::services::validate_email;
+use crate::lockout::model::LockoutStatusResponse;
+use crate::lockout::repository::{
+    active_lock_repository, clear_failed_logins_repository, list_active_locks_repository,
+    lock_login_repository, lockout_status_repository, record_failed_login_repository,
+};
+
+// Password logins only; every caller passes the normalized email whether or not an account exists
+
+pub async fn ensure_not_locked_service(email: &str, pool: &PgPool) -> AppResult<()> {
+    if let Some(locked_until) = active_lock_repository(pool, email).await? {
+        let remaining = (locked_until - Utc::now()).num_milliseconds();
+        return Err(AppError::AccountLocked {
+            retry_after_seconds: (remaining.max(0) as u64).div_ceil(1000).max(1),
+        });
+    }
+    Ok(())
+}
+
+pub async fn record_failed_login_service(
+    email: &str,
+    pool: &PgPool,
+    config: &AuthConfig,
+) -> AppResult<()> {
+    let failed_count = i64::from(record_failed_login_repository(pool, email).await?);
+    if failed_count < config.lockout_threshold {
+        return Ok(());
+    }
+
+    // lockout_base_seconds, doubled for every failure past the threshold
+    let doublings = (failed_count - config.lockout_threshold).min(32) as u32;
+    let seconds = config
+        .lockout_base_seconds
+        .saturating_mul(1_i64 << doublings)
+        .min(config.lockout_max_seconds);
+
+    lock_login_repository(pool, email, Utc::now() + Duration::seconds(seconds)).await?;
+    tracing::warn!(email, failed_count, seconds, "Login locked after repeated failures");
+    Ok(())
+}
+
+pub async fn clear_failed_logins_service(email: &str, pool: &PgPool) -> AppResult<()> {
+    clear_failed_logins_repository(pool, email).await?;
+    Ok(())
+}
+
+pub async fn list_lockouts_service(pool: &PgPool) -> AppResult<Vec<LockoutStatusResponse>> {
+    list_active_locks_repository(pool).await
+}
+
+// Addresses with no recorded failures report a clean state rather than 404
+pub async fn lockout_status_service(email: &str, pool: &PgPool) -> AppResult<LockoutStatusResponse> {
+    validate_email(email)?;
+    let normalized_email = email.to_lowercase().trim().to_string();
+
+    Ok(lockout_status_repository(pool, &normalized_email)
+        .await?
+        .unwrap_or(LockoutStatusResponse {
+            email: normalized_email,
+            failed_count: 0,
+            last_failed_at: None,
+            locked_until: None,
+        }))
+}
+
+pub async fn unlock_service(actor_id: i32, email: &str, pool: &PgPool) -> AppResult<()> {
+    validate_email(email)?;
+    let normalized_email = email.to_lowercase().trim().to_string();
+
+    if !clear_failed_logins_repository(pool, &normalized_email).await? {
+        return Err(AppError::not_found("Lockout"));
+    }
+    tracing::info!(actor_id, email = %normalized_email, "Login lockout cleared");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::{header, Method, StatusCode};
+    use axum::response::IntoResponse;
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::grouped_routes::main_route::AppState;
+    use crate::test_support::{load_env, send_json, test_state, wait_for_mail, RecordingMailer, TestResponse};
+
+    const KNOWN: &str = "known@example.com";
+    const UNKNOWN: &str = "unknown@example.com";
+    const PASSWORD: &str = "Correct-Horse-Battery-9";
+
+    fn config() -> AuthConfig {
+        load_env();
+        AuthConfig {
+            lockout_threshold: 3,
+            lockout_base_seconds: 30,
+            lockout_max_seconds: 100,
+            ..AuthConfig::from_env()
+        }
+    }
+
+    async fn locked_for(email: &str, pool: &PgPool) -> Option<u64> {
+        match ensure_not_locked_service(email, pool).await {
+            Ok(()) => None,
+            Err(AppError::AccountLocked { retry_after_seconds }) => Some(retry_after_seconds),
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    async fn fail(email: &str, times: usize, pool: &PgPool, config: &AuthConfig) {
+        for _ in 0..times {
+            record_failed_login_service(email, pool, config).await.unwrap();
+        }
+    }
+
+    #[sqlx::test]
+    async fn locks_once_the_threshold_is_reached(pool: PgPool) {
+        let config = config();
+
+        fail(KNOWN, 2, &pool, &config).await;
+        assert_eq!(locked_for(KNOWN, &pool).await, None);
+
+        fail(KNOWN, 1, &pool, &config).await;
+        let retry_after = locked_for(KNOWN, &pool).await.unwrap();
+        assert!((29..=30).contains(&retry_after), "locked for {retry_after}s");
+    }
+
+    #[sqlx::test]
+    async fn backoff_doubles_up_to_the_maximum(pool: PgPool) {
+        let config = config();
+        fail(KNOWN, 2, &pool, &config).await;
+
+        for expected in [30, 60, 100, 100] {
+            fail(KNOWN, 1, &pool, &config).await;
+            let retry_after = locked_for(KNOWN, &pool).await.unwrap();
+            assert!((expected - 1..=expected).contains(&retry_after), "locked for {retry_after}s, expected {expected}s");
+        }
+    }
+
+    #[sqlx::test]
+    async fn failures_more_than_a_day_old_start_the_count_again(pool: PgPool) {
+        let config = config();
+        fail(KNOWN, 2, &pool, &config).await;
+        sqlx::query!("UPDATE login_attempts SET last_failed_at = NOW() - INTERVAL '1 day 1 second'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        fail(KNOWN, 1, &pool, &config).await;
+
+        assert_eq!(lockout_status_service(KNOWN, &pool).await.unwrap().failed_count, 1);
+        assert_eq!(locked_for(KNOWN, &pool).await, None);
+    }
+
+    async fn login(state: &AppState, email: &str, password: &str) -> TestResponse {
+        send_json(state, Method::POST, "/crud/login", json!({ "email": email, "password": password })).await
+    }
+
+    #[sqlx::test]
+    async fn locked_logins_answer_account_locked_with_retry_after(pool: PgPool) {
+        let mailer = Arc::new(RecordingMailer::default());
+        let state = test_state(pool, mailer.clone(), |config| {
+            config.allow_unverified_login = true;
+            config.lockout_threshold = 3;
+            config.lockout_base_seconds = 30;
+        });
+        send_json(&state, Method::POST, "/credentials", json!({ "email": KNOWN, "password": PASSWORD })).await;
+        wait_for_mail(&mailer, KNOWN).await;
+
+        for _ in 0..3 {
+            assert_eq!(login(&state, KNOWN, "Wrong-Password-1").await.status, StatusCode::UNAUTHORIZED);
+        }
+
+        // Even the right password is refused until the lock runs out
+        let locked = login(&state, KNOWN, PASSWORD).await;
+        assert_eq!(locked.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(locked.json()["error"], "ACCOUNT_LOCKED");
+        let retry_after: u64 = locked.headers[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!((29..=30).contains(&retry_after), "Retry-After: {retry_after}");
+    }
+
+    #[sqlx::test]
+    async fn unknown_emails_lock_exactly_like_known_ones(pool: PgPool) {
+        let mailer = Arc::new(RecordingMailer::default());
+        let state = test_state(pool, mailer.clone(), |config| {
+            config.lockout_threshold = 3;
+            config.lockout_base_seconds = 30;
+        });
+        send_json(&state, Method::POST, "/credentials", json!({ "email": KNOWN, "password": PASSWORD })).await;
+        wait_for_mail(&mailer, KNOWN).await;
+
+        for _ in 0..4 {
+            let known = login(&state, KNOWN, "Wrong-Password-1").await;
+            let unknown = login(&state, UNKNOWN, "Wrong-Password-1").await;
+            assert_eq!(known.status, unknown.status);
+            assert_eq!(known.body, unknown.body);
+            assert_eq!(known.headers.contains_key(header::RETRY_AFTER), unknown.headers.contains_key(header::RETRY_AFTER));
+        }
+
+        let known = lockout_status_service(KNOWN, &state.db).await.unwrap();
+        let unknown = lockout_status_service(UNKNOWN, &state.db).await.unwrap();
+        assert_eq!(known.failed_count, unknown.failed_count);
+        assert!(known.locked_until.is_some() && unknown.locked_until.is_some());
+    }
+
+    #[sqlx::test]
+    async fn unlocking_an_address_without_failures_is_not_found(pool: PgPool) {
+        let config = config();
+
+        let missing = unlock_service(1, UNKNOWN, &pool).await.unwrap_err();
+        assert_eq!(missing.into_response().status(), StatusCode::NOT_FOUND);
+
+        fail(KNOWN, 3, &pool, &config).await;
+        unlock_service(1, KNOWN, &pool).await.unwrap();
+        assert_eq!(locked_for(KNOWN, &pool).await, None);
+    }
+}
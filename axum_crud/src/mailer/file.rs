@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::mailer::{render_message, Email, Mailer};
+
+// Writes each message to `<outbox>/<timestamp>-<uuid>.eml` so tests and local runs can read links back
+pub struct FileMailer {
+    from: String,
+    outbox: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(from: String, outbox: impl Into<PathBuf>) -> Self {
+        Self {
+            from,
+            outbox: outbox.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> AppResult<()> {
+        tokio::fs::create_dir_all(&self.outbox)
+            .await
+            .map_err(|err| AppError::internal(format!("Failed to create mail outbox: {err}")))?;
+
+        let file_name = format!("{}-{}.eml", chrono::Utc::now().format("%Y%m%dT%H%M%S%.6fZ"), Uuid::new_v4());
+        tokio::fs::write(self.outbox.join(file_name), render_message(&self.from, &email))
+            .await
+            .map_err(|err| AppError::internal(format!("Failed to write mail to outbox: {err}")))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+pub mod stdout;
+pub mod file;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::crud::error_traits::AppResult;
+use crate::mailer::file::FileMailer;
+use crate::mailer::stdout::StdoutMailer;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Outgoing mail goes through this trait so flows can run without an SMTP server
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> AppResult<()>;
+}
+
+// MAILER=stdout (default) logs messages, MAILER=file writes one .eml per message into MAIL_OUTBOX_DIR
+pub fn mailer_from_env() -> Arc<dyn Mailer> {
+    let from = std::env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string());
+
+    match std::env::var("MAILER").as_deref() {
+        Ok("stdout") | Err(_) => Arc::new(StdoutMailer::new(from)),
+        Ok("file") => {
+            let outbox = std::env::var("MAIL_OUTBOX_DIR").unwrap_or_else(|_| "outbox".to_string());
+            Arc::new(FileMailer::new(from, outbox))
+        }
+        Ok(other) => panic!("Unsupported MAILER: {other} (expected stdout or file)"),
+    }
+}
+
+// Delivery time would otherwise show in the response time of flows that only mail existing accounts
+pub fn send_in_background(mailer: Arc<dyn Mailer>, email: Email) {
+    tokio::spawn(async move {
+        if let Err(err) = mailer.send(email).await {
+            tracing::error!("Failed to send email: {}", err);
+        }
+    });
+}
+
+pub(crate) fn render_message(from: &str, email: &Email) -> String {
+    format!(
+        "From: {from}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        email.to, email.subject, email.body
+    )
+}
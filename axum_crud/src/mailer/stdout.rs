@@ -0,0 +1,23 @@
+use async_trait::async_trait;
+
+use crate::crud::error_traits::AppResult;
+use crate::mailer::{render_message, Email, Mailer};
+
+// Development mailer: prints every message to stdout instead of delivering it
+pub struct StdoutMailer {
+    from: String,
+}
+
+impl StdoutMailer {
+    pub fn new(from: String) -> Self {
+        Self { from }
+    }
+}
+
+#[async_trait]
+impl Mailer for StdoutMailer {
+    async fn send(&self, email: Email) -> AppResult<()> {
+        println!("{}", render_message(&self.from, &email));
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::crud::error_traits::{AppError, AppResult};
+
+const NONCE_LEN: usize = 12;
+
+// AES-256-GCM for secrets we must be able to read back (unlike passwords and tokens, which are hashed).
+// Stored layout is nonce || ciphertext; the associated data ties a ciphertext to its owning row.
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    // TOTP_ENCRYPTION_KEY is 32 random bytes, base64 encoded (e.g. `openssl rand -base64 32`)
+    pub fn from_env() -> Self {
+        let encoded = std::env::var("TOTP_ENCRYPTION_KEY").expect("TOTP_ENCRYPTION_KEY must be set");
+        let key = STANDARD
+            .decode(encoded.trim())
+            .expect("TOTP_ENCRYPTION_KEY must be base64");
+        assert_eq!(key.len(), 32, "TOTP_ENCRYPTION_KEY must decode to 32 bytes");
+
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> AppResult<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: plaintext, aad: associated_data })
+            .map_err(|_| AppError::internal("Failed to encrypt secret"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn decrypt(&self, sealed: &[u8], associated_data: &[u8]) -> AppResult<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(AppError::internal("Encrypted secret is truncated"));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: associated_data })
+            .map_err(|_| AppError::internal("Failed to decrypt secret"))
+    }
+}
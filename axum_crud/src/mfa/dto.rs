@@ -0,0 +1,24 @@
+
+use serde::{Deserialize};
+
+// `code` is a TOTP code or, wherever a second factor is checked after enrollment, a recovery code
+#[derive(Deserialize,Debug)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+// Second login step; `session: true` answers with a session cookie instead of bearer tokens
+#[derive(Deserialize,Debug)]
+pub struct MfaVerifyRequest {
+    pub mfa_token: String,
+    pub code: String,
+    #[serde(default)]
+    pub session: bool,
+}
+
+// Turning 2FA off needs both factors again, not just a (possibly stolen) access token
+#[derive(Deserialize,Debug)]
+pub struct DisableTotpRequest {
+    pub password: String,
+    pub code: String,
+}
@@ -0,0 +1,85 @@
+use axum::{
+    extract::{State},
+    Json,
+    response::{IntoResponse, Response},
+    http::StatusCode,
+};
+use axum_extra::extract::cookie::CookieJar;
+use crate::auth::extractor::Authenticated;
+use crate::auth::handler::completed_login_response;
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::mfa::dto::{TotpCodeRequest,MfaVerifyRequest,DisableTotpRequest};
+use crate::mfa::services::{
+    mfa_status_service,enroll_totp_service,confirm_totp_service,
+    verify_mfa_login_service,disable_totp_service,
+    recovery_codes_status_service,regenerate_recovery_codes_service,
+};
+
+#[axum::debug_handler]
+pub async fn mfa_status_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    let response = mfa_status_service(&auth, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn enroll_totp_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    let response = enroll_totp_service(&auth, &state.db, &state.totp).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn confirm_totp_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Json(body): Json<TotpCodeRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = confirm_totp_service(&auth, body, &state.db, &state.totp).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn disable_totp_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Json(body): Json<DisableTotpRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = disable_totp_service(&auth, body, &state.db, &state.totp, &state.passwords, &state.auth_config).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// Exchanges the mfa_token from the password step for bearer tokens or a session cookie
+#[axum::debug_handler]
+pub async fn verify_mfa_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(body): Json<MfaVerifyRequest>,
+) -> AppResult<Response> {
+    let verified = verify_mfa_login_service(&body, &state.db, &state.jwt, &state.totp, &state.auth_config).await?;
+    completed_login_response(&state, jar, verified.credential_id, verified.email, body.session).await
+}
+
+#[axum::debug_handler]
+pub async fn recovery_codes_status_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    let response = recovery_codes_status_service(&auth, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn regenerate_recovery_codes_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Json(body): Json<TotpCodeRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = regenerate_recovery_codes_service(&auth, body, &state.db, &state.totp).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
@@ -0,0 +1,9 @@
+pub mod crypto;
+pub mod totp;
+pub mod recovery;
+pub mod dto;
+pub mod model;
+pub mod repository;
+pub mod services;
+pub mod handler;
+pub mod routes;
@@ -0,0 +1,56 @@
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize};
+use uuid::Uuid;
+
+
+#[derive(Serialize)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_png_base64: String,
+    pub qr_svg: String,
+}
+
+#[derive(Serialize)]
+pub struct MfaStatusResponse {
+    pub totp_enabled: bool,
+}
+
+// Returned once, when codes are generated; only their hashes are kept
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub totp_enabled: bool,
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesStatusResponse {
+    pub remaining: i64,
+}
+
+pub struct StoredTotpSecret {
+    pub secret_encrypted: Vec<u8>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+// Credential that passed the password step and still owes a second factor
+pub struct MfaChallenge {
+    pub mfa_token: String,
+    pub expires_in: u64,
+    // Second factors the account can complete with: "totp" and/or "webauthn"
+    pub methods: Vec<String>,
+}
+
+// A decoded mfa_token whose row is still unspent
+pub struct PendingMfaLogin {
+    pub id: Uuid,
+    pub credential_id: i32,
+    pub email: String,
+}
+
+#[derive(Debug)]
+pub struct VerifiedMfaLogin {
+    pub credential_id: i32,
+    pub email: String,
+}
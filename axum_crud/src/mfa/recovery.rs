@@ -0,0 +1,34 @@
+use rand::{rngs::OsRng, Rng};
+
+use crate::auth::tokens::hash_token;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+// No 0/o/1/l/i so codes survive being read off paper
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+// Codes are shown as `xxxxx-xxxxx`; each carries ~50 bits, so a fast hash is enough to store them
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = (0..RECOVERY_CODE_LEN)
+                .map(|_| RECOVERY_CODE_ALPHABET[OsRng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &code[..RECOVERY_CODE_LEN / 2], &code[RECOVERY_CODE_LEN / 2..])
+        })
+        .collect()
+}
+
+// Users type these back loosely, so hyphens, spaces and case are ignored
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_token(&normalized)
+}
+
+pub fn looks_like_recovery_code(code: &str) -> bool {
+    code.chars().filter(|c| c.is_ascii_alphanumeric()).count() == RECOVERY_CODE_LEN
+}
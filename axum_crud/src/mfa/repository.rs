@@ -0,0 +1,272 @@
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::crud::error_traits::AppResult;
+use crate::mfa::model::StoredTotpSecret;
+
+
+// Re-enrolling before confirmation replaces the pending secret; a confirmed one is left alone
+pub async fn upsert_pending_totp_secret_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    secret_encrypted: &[u8],
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO totp_secrets (credential_id, secret_encrypted)
+        VALUES ($1, $2)
+        ON CONFLICT (credential_id) DO UPDATE
+        SET secret_encrypted = EXCLUDED.secret_encrypted, last_used_step = NULL, created_at = NOW()
+        WHERE totp_secrets.confirmed_at IS NULL
+        "#,
+        credential_id,
+        secret_encrypted
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn find_totp_secret_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<Option<StoredTotpSecret>> {
+    let record = sqlx::query!(
+        "SELECT secret_encrypted, confirmed_at FROM totp_secrets WHERE credential_id = $1",
+        credential_id
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|r| StoredTotpSecret {
+        secret_encrypted: r.secret_encrypted,
+        confirmed_at: r.confirmed_at,
+    }))
+}
+
+pub async fn is_totp_enabled_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<bool> {
+    let enabled = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM totp_secrets WHERE credential_id = $1 AND confirmed_at IS NOT NULL
+        ) AS "enabled!"
+        "#,
+        credential_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(enabled)
+}
+
+// Atomically records `step` as used; false means this code (or a later one) was already accepted
+pub async fn consume_totp_step_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    step: i64,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE totp_secrets
+        SET last_used_step = $2
+        WHERE credential_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+        "#,
+        credential_id,
+        step
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn confirm_totp_secret_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "UPDATE totp_secrets SET confirmed_at = NOW() WHERE credential_id = $1",
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_totp_secret_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM totp_secrets WHERE credential_id = $1",
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+
+// Replaces the whole set so a regenerated batch invalidates every earlier code
+pub async fn replace_recovery_codes_repository(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    code_hashes: &[String],
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM mfa_recovery_codes WHERE credential_id = $1",
+        credential_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO mfa_recovery_codes (credential_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::VARCHAR[]) AS code_hash
+        "#,
+        credential_id,
+        code_hashes
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn consume_recovery_code_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    code_hash: &str,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE mfa_recovery_codes SET used_at = NOW()
+        WHERE credential_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        credential_id,
+        code_hash
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn count_remaining_recovery_codes_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<i64> {
+    let remaining = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "remaining!" FROM mfa_recovery_codes
+        WHERE credential_id = $1 AND used_at IS NULL
+        "#,
+        credential_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(remaining)
+}
+
+pub async fn delete_recovery_codes_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM mfa_recovery_codes WHERE credential_id = $1",
+        credential_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn insert_pending_login_repository(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+    credential_id: i32,
+    expires_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query!(
+        "INSERT INTO mfa_pending_logins (id, credential_id, expires_at) VALUES ($1, $2, $3)",
+        id,
+        credential_id,
+        expires_at
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn is_pending_login_active_repository(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+    credential_id: i32,
+) -> AppResult<bool> {
+    let active = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM mfa_pending_logins
+            WHERE id = $1 AND credential_id = $2 AND consumed_at IS NULL AND expires_at > NOW()
+        ) AS "active!"
+        "#,
+        id,
+        credential_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(active)
+}
+
+// Atomic, so two requests racing with the same token can't both complete the login
+pub async fn consume_pending_login_repository(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE mfa_pending_logins SET consumed_at = NOW()
+        WHERE id = $1 AND consumed_at IS NULL AND expires_at > NOW()
+        "#,
+        id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Burns the token once it reaches `max_attempts` wrong codes
+pub async fn record_failed_pending_login_repository(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+    max_attempts: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        UPDATE mfa_pending_logins
+        SET failed_attempts = failed_attempts + 1,
+            consumed_at = CASE WHEN failed_attempts + 1 >= $2 THEN NOW() END
+        WHERE id = $1 AND consumed_at IS NULL
+        "#,
+        id,
+        max_attempts
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use axum::{
+    routing::{get, post},
+    Router
+};
+use crate::mfa::handler::{
+    mfa_status_handler,enroll_totp_handler,confirm_totp_handler,disable_totp_handler,verify_mfa_handler,
+    recovery_codes_status_handler,regenerate_recovery_codes_handler,
+};
+use crate::grouped_routes::main_route::AppState;
+
+pub fn mfa_routes() -> Router<AppState> {
+    Router::new()
+      .route("/", get(mfa_status_handler))
+      .route("/verify", post(verify_mfa_handler))
+      .route("/totp/enroll", post(enroll_totp_handler))
+      .route("/totp/confirm", post(confirm_totp_handler))
+      .route("/totp/disable", post(disable_totp_handler))
+      .route("/recovery_codes", get(recovery_codes_status_handler))
+      .route("/recovery_codes/regenerate", post(regenerate_recovery_codes_handler))
+}
@@ -0,0 +1,504 @@
+
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::auth::config::AuthConfig;
+use crate::auth::extractor::Authenticated;
+use crate::auth::jwt::JwtKeys;
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::repository::get_stored_credentials_by_id_repository;
+use crate::mfa::dto::{DisableTotpRequest, MfaVerifyRequest, TotpCodeRequest};
+use crate::mfa::model::{
+    MfaChallenge, MfaStatusResponse, PendingMfaLogin, RecoveryCodesResponse,
+    RecoveryCodesStatusResponse, TotpEnrollmentResponse, VerifiedMfaLogin,
+};
+use crate::mfa::recovery::{generate_recovery_codes, hash_recovery_code, looks_like_recovery_code};
+use crate::mfa::repository::{
+    confirm_totp_secret_repository, consume_pending_login_repository, consume_recovery_code_repository,
+    consume_totp_step_repository, count_remaining_recovery_codes_repository,
+    delete_recovery_codes_repository, delete_totp_secret_repository, find_totp_secret_repository,
+    insert_pending_login_repository, is_pending_login_active_repository, is_totp_enabled_repository,
+    record_failed_pending_login_repository, replace_recovery_codes_repository,
+    upsert_pending_totp_secret_repository,
+};
+use crate::mfa::totp::{encoded_secret, generate_secret, qr_png_base64, qr_svg, verify_code, TotpConfig};
+use crate::webauthn::repository::has_passkeys_repository;
+use crate::lockout::services::{clear_failed_logins_service, ensure_not_locked_service, record_failed_login_service};
+use crate::passwords::pool::HashingPool;
+
+const MFA_PENDING_PURPOSE: &str = "mfa_pending";
+
+
+// Called after the password check; Some means the caller must finish with /auth/mfa/verify
+// (TOTP or recovery code) or /auth/webauthn/login/finish (passkey)
+pub async fn mfa_challenge_service(
+    credential_id: i32,
+    email: &str,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    totp: &TotpConfig,
+) -> AppResult<Option<MfaChallenge>> {
+    let mut methods = Vec::new();
+    if is_totp_enabled_repository(pool, credential_id).await? {
+        methods.push("totp".to_string());
+    }
+    if has_passkeys_repository(pool, credential_id).await? {
+        methods.push("webauthn".to_string());
+    }
+    if methods.is_empty() {
+        return Ok(None);
+    }
+
+    let (mfa_token, pending_id) = jwt.issue_single_use_purpose_token(
+        MFA_PENDING_PURPOSE,
+        credential_id,
+        email,
+        totp.mfa_pending_token_ttl_seconds,
+    )?;
+    let expires_at = Utc::now() + Duration::seconds(totp.mfa_pending_token_ttl_seconds as i64);
+    insert_pending_login_repository(pool, pending_id, credential_id, expires_at).await?;
+
+    Ok(Some(MfaChallenge {
+        mfa_token,
+        expires_in: totp.mfa_pending_token_ttl_seconds,
+        methods,
+    }))
+}
+
+// Also checks the token's row, so a consumed or burned token is rejected even before it expires
+pub async fn decode_mfa_token(mfa_token: &str, pool: &PgPool, jwt: &JwtKeys) -> AppResult<PendingMfaLogin> {
+    let invalid = || AppError::authentication("MFA token is invalid or has expired");
+
+    let claims = jwt
+        .decode_purpose_token(MFA_PENDING_PURPOSE, mfa_token)
+        .map_err(|_| invalid())?;
+    let id = claims.nonce.ok_or_else(invalid)?;
+    let credential_id = claims.credential_id()?;
+
+    if !is_pending_login_active_repository(pool, id, credential_id).await? {
+        return Err(invalid());
+    }
+
+    Ok(PendingMfaLogin {
+        id,
+        credential_id,
+        email: claims.email,
+    })
+}
+
+// Decrypts the stored secret, checks the code and burns its time step so it can't be replayed
+async fn check_totp_code(
+    credential_id: i32,
+    email: &str,
+    code: &str,
+    require_confirmed: bool,
+    pool: &PgPool,
+    totp: &TotpConfig,
+) -> AppResult<()> {
+    let invalid = || AppError::authentication("Invalid verification code");
+
+    let stored = find_totp_secret_repository(pool, credential_id)
+        .await?
+        .filter(|stored| !require_confirmed || stored.confirmed_at.is_some())
+        .ok_or_else(invalid)?;
+
+    let secret = totp.decrypt_secret(credential_id, &stored.secret_encrypted)?;
+    let step = verify_code(&totp.build(secret, email)?, code).ok_or_else(invalid)?;
+
+    if !consume_totp_step_repository(pool, credential_id, step).await? {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+// Second factor for an enrolled account: a TOTP code, or one unused recovery code
+async fn check_second_factor(
+    credential_id: i32,
+    email: &str,
+    code: &str,
+    pool: &PgPool,
+    totp: &TotpConfig,
+) -> AppResult<()> {
+    if !looks_like_recovery_code(code) {
+        return check_totp_code(credential_id, email, code, true, pool, totp).await;
+    }
+
+    if !is_totp_enabled_repository(pool, credential_id).await?
+        || !consume_recovery_code_repository(pool, credential_id, &hash_recovery_code(code)).await?
+    {
+        return Err(AppError::authentication("Invalid verification code"));
+    }
+
+    tracing::info!(credential_id, "Recovery code used");
+    Ok(())
+}
+
+// Stores a fresh batch (dropping any previous one) and returns the plaintext codes once
+async fn issue_recovery_codes(credential_id: i32, pool: &PgPool) -> AppResult<Vec<String>> {
+    let codes = generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|code| hash_recovery_code(code)).collect();
+
+    let mut tx = pool.begin().await?;
+    replace_recovery_codes_repository(&mut tx, credential_id, &hashes).await?;
+    tx.commit().await?;
+
+    Ok(codes)
+}
+
+pub async fn mfa_status_service(
+    auth: &Authenticated,
+    pool: &PgPool,
+) -> AppResult<MfaStatusResponse> {
+    Ok(MfaStatusResponse {
+        totp_enabled: is_totp_enabled_repository(pool, auth.credential_id()).await?,
+    })
+}
+
+pub async fn enroll_totp_service(
+    auth: &Authenticated,
+    pool: &PgPool,
+    totp: &TotpConfig,
+) -> AppResult<TotpEnrollmentResponse> {
+    let secret = generate_secret();
+    let encrypted = totp.encrypt_secret(auth.credential_id(), &secret)?;
+
+    if !upsert_pending_totp_secret_repository(pool, auth.credential_id(), &encrypted).await? {
+        return Err(AppError::conflict("Two-factor authentication is already enabled"));
+    }
+
+    let otpauth_uri = totp.build(secret.clone(), auth.email())?.get_url();
+
+    Ok(TotpEnrollmentResponse {
+        secret: encoded_secret(&secret),
+        qr_png_base64: qr_png_base64(&otpauth_uri)?,
+        qr_svg: qr_svg(&otpauth_uri)?,
+        otpauth_uri,
+    })
+}
+
+pub async fn confirm_totp_service(
+    auth: &Authenticated,
+    input: TotpCodeRequest,
+    pool: &PgPool,
+    totp: &TotpConfig,
+) -> AppResult<RecoveryCodesResponse> {
+    if is_totp_enabled_repository(pool, auth.credential_id()).await? {
+        return Err(AppError::conflict("Two-factor authentication is already enabled"));
+    }
+
+    check_totp_code(auth.credential_id(), auth.email(), &input.code, false, pool, totp).await?;
+    confirm_totp_secret_repository(pool, auth.credential_id()).await?;
+
+    Ok(RecoveryCodesResponse {
+        totp_enabled: true,
+        recovery_codes: issue_recovery_codes(auth.credential_id(), pool).await?,
+    })
+}
+
+pub async fn recovery_codes_status_service(
+    auth: &Authenticated,
+    pool: &PgPool,
+) -> AppResult<RecoveryCodesStatusResponse> {
+    Ok(RecoveryCodesStatusResponse {
+        remaining: count_remaining_recovery_codes_repository(pool, auth.credential_id()).await?,
+    })
+}
+
+// Needs a current second factor so a hijacked session alone can't mint new codes
+pub async fn regenerate_recovery_codes_service(
+    auth: &Authenticated,
+    input: TotpCodeRequest,
+    pool: &PgPool,
+    totp: &TotpConfig,
+) -> AppResult<RecoveryCodesResponse> {
+    check_second_factor(auth.credential_id(), auth.email(), &input.code, pool, totp).await?;
+
+    Ok(RecoveryCodesResponse {
+        totp_enabled: true,
+        recovery_codes: issue_recovery_codes(auth.credential_id(), pool).await?,
+    })
+}
+
+// Wrong codes count towards the same per-email lockout as wrong passwords, and each mfa_token
+// is spent on success or after `mfa_max_attempts` wrong codes
+pub async fn verify_mfa_login_service(
+    input: &MfaVerifyRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    totp: &TotpConfig,
+    config: &AuthConfig,
+) -> AppResult<VerifiedMfaLogin> {
+    let pending = decode_mfa_token(&input.mfa_token, pool, jwt).await?;
+    ensure_not_locked_service(&pending.email, pool).await?;
+
+    if let Err(err) = check_second_factor(pending.credential_id, &pending.email, &input.code, pool, totp).await {
+        if matches!(err, AppError::Authentication { .. }) {
+            record_failed_pending_login_repository(pool, pending.id, totp.mfa_max_attempts).await?;
+            record_failed_login_service(&pending.email, pool, config).await?;
+        }
+        return Err(err);
+    }
+
+    if !consume_pending_login_repository(pool, pending.id).await? {
+        return Err(AppError::authentication("MFA token is invalid or has expired"));
+    }
+    clear_failed_logins_service(&pending.email, pool).await?;
+
+    Ok(VerifiedMfaLogin {
+        credential_id: pending.credential_id,
+        email: pending.email,
+    })
+}
+
+// A wrong password counts as a failed login, so a hijacked session can't be used to guess it
+pub async fn disable_totp_service(
+    auth: &Authenticated,
+    input: DisableTotpRequest,
+    pool: &PgPool,
+    totp: &TotpConfig,
+    passwords: &HashingPool,
+    config: &AuthConfig,
+) -> AppResult<MfaStatusResponse> {
+    let credentials = get_stored_credentials_by_id_repository(auth.credential_id(), pool)
+        .await?
+        .ok_or_else(|| AppError::authentication("Invalid password"))?;
+
+    ensure_not_locked_service(&credentials.email, pool).await?;
+    if !passwords.verify(&input.password, &credentials.password_hash, credentials.password_scheme.as_deref()).await? {
+        record_failed_login_service(&credentials.email, pool, config).await?;
+        return Err(AppError::authentication("Invalid password"));
+    }
+    clear_failed_logins_service(&credentials.email, pool).await?;
+
+    check_second_factor(auth.credential_id(), auth.email(), &input.code, pool, totp).await?;
+
+    let mut tx = pool.begin().await?;
+    delete_totp_secret_repository(&mut *tx, auth.credential_id()).await?;
+    delete_recovery_codes_repository(&mut *tx, auth.credential_id()).await?;
+    tx.commit().await?;
+
+    Ok(MfaStatusResponse { totp_enabled: false })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use jsonwebtoken::get_current_timestamp;
+    use sqlx::PgPool;
+    use totp_rs::{Secret, TOTP};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::auth::extractor::AuthUser;
+    use crate::grouped_routes::main_route::AppState;
+    use crate::test_support::{create_account, load_env, test_state, RecordingMailer};
+
+    const EMAIL: &str = "mfa@example.com";
+    const PASSWORD: &str = "Correct-Horse-Battery-9";
+
+    struct Enrolled {
+        state: AppState,
+        auth: Authenticated,
+        totp: TOTP,
+        // Step whose code confirmed the enrollment, and is therefore spent
+        confirmed_step: u64,
+        recovery_codes: Vec<String>,
+    }
+
+    fn current_step() -> u64 {
+        get_current_timestamp() / 30
+    }
+
+    fn code_at(totp: &TOTP, step: u64) -> String {
+        totp.generate(step * 30)
+    }
+
+    // A well-formed code that matches no step in the accepted window
+    fn wrong_code(totp: &TOTP) -> String {
+        let step = current_step();
+        let window: Vec<String> = (step - 2..=step + 2).map(|step| code_at(totp, step)).collect();
+        (0..)
+            .map(|n| format!("{n:06}"))
+            .find(|code| !window.contains(code))
+            .unwrap()
+    }
+
+    fn rejected(result: AppResult<impl Sized>, reason: &str) {
+        match result {
+            Err(AppError::Authentication { reason: actual }) => assert_eq!(actual, reason),
+            Err(err) => panic!("expected authentication failure, got {err}"),
+            Ok(_) => panic!("expected authentication failure, got success"),
+        }
+    }
+
+    // An account with a password and TOTP enrolled and confirmed
+    async fn enrolled(pool: PgPool) -> Enrolled {
+        let state = test_state(pool, Arc::new(RecordingMailer::default()), |_| {});
+        let id = create_account(&state.db, EMAIL).await;
+        let hash = state.passwords.hash(PASSWORD).await.unwrap();
+        sqlx::query!("UPDATE credentials SET password = $1 WHERE id = $2", hash, id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let auth = Authenticated::Bearer(AuthUser {
+            id,
+            email: EMAIL.to_string(),
+            jti: Uuid::new_v4(),
+            expires_at: Utc::now(),
+        });
+
+        let enrollment = enroll_totp_service(&auth, &state.db, &state.totp).await.unwrap();
+        let secret = Secret::Encoded(enrollment.secret).to_bytes().unwrap();
+        let totp = state.totp.build(secret, EMAIL).unwrap();
+
+        let confirmed_step = current_step();
+        let code = TotpCodeRequest { code: code_at(&totp, confirmed_step) };
+        let confirmed = confirm_totp_service(&auth, code, &state.db, &state.totp).await.unwrap();
+
+        Enrolled {
+            state,
+            auth,
+            totp,
+            confirmed_step,
+            recovery_codes: confirmed.recovery_codes,
+        }
+    }
+
+    async fn start_login(enrolled: &Enrolled) -> String {
+        let state = &enrolled.state;
+        mfa_challenge_service(enrolled.auth.credential_id(), EMAIL, &state.db, &state.jwt, &state.totp)
+            .await
+            .unwrap()
+            .expect("TOTP is enabled, so a second step is required")
+            .mfa_token
+    }
+
+    async fn verify(enrolled: &Enrolled, mfa_token: &str, code: &str, totp: &TotpConfig) -> AppResult<VerifiedMfaLogin> {
+        let input = MfaVerifyRequest {
+            mfa_token: mfa_token.to_string(),
+            code: code.to_string(),
+            session: false,
+        };
+        let state = &enrolled.state;
+        verify_mfa_login_service(&input, &state.db, &state.jwt, totp, &state.auth_config).await
+    }
+
+    async fn failed_logins(state: &AppState) -> i32 {
+        sqlx::query_scalar!("SELECT failed_count FROM login_attempts WHERE email = $1", EMAIL)
+            .fetch_optional(&state.db)
+            .await
+            .unwrap()
+            .unwrap_or(0)
+    }
+
+    #[sqlx::test]
+    async fn a_code_is_not_accepted_twice_in_the_same_step(pool: PgPool) {
+        let enrolled = enrolled(pool).await;
+        let mfa_token = start_login(&enrolled).await;
+
+        let replayed = code_at(&enrolled.totp, enrolled.confirmed_step);
+        rejected(verify(&enrolled, &mfa_token, &replayed, &enrolled.state.totp).await, "Invalid verification code");
+
+        let next = code_at(&enrolled.totp, enrolled.confirmed_step + 1);
+        let verified = verify(&enrolled, &mfa_token, &next, &enrolled.state.totp).await.unwrap();
+        assert_eq!(verified.credential_id, enrolled.auth.credential_id());
+
+        // The step is spent, and so is the mfa_token
+        rejected(verify(&enrolled, &mfa_token, &next, &enrolled.state.totp).await, "MFA token is invalid or has expired");
+    }
+
+    #[sqlx::test]
+    async fn wrong_codes_count_towards_the_limit_and_burn_the_pending_token(pool: PgPool) {
+        let enrolled = enrolled(pool).await;
+        load_env();
+        let totp = TotpConfig {
+            mfa_max_attempts: 2,
+            ..TotpConfig::from_env()
+        };
+        let mfa_token = start_login(&enrolled).await;
+        let wrong = wrong_code(&enrolled.totp);
+
+        rejected(verify(&enrolled, &mfa_token, &wrong, &totp).await, "Invalid verification code");
+        assert_eq!(failed_logins(&enrolled.state).await, 1);
+        rejected(verify(&enrolled, &mfa_token, &wrong, &totp).await, "Invalid verification code");
+        assert_eq!(failed_logins(&enrolled.state).await, 2);
+
+        // Exhausted: even a valid code no longer completes this login
+        let valid = code_at(&enrolled.totp, enrolled.confirmed_step + 1);
+        rejected(verify(&enrolled, &mfa_token, &valid, &totp).await, "MFA token is invalid or has expired");
+
+        // A fresh password step gets a fresh token
+        let fresh_token = start_login(&enrolled).await;
+        verify(&enrolled, &fresh_token, &valid, &totp).await.unwrap();
+    }
+
+    async fn disable(enrolled: &Enrolled, password: &str, code: &str) -> AppResult<MfaStatusResponse> {
+        let input = DisableTotpRequest {
+            password: password.to_string(),
+            code: code.to_string(),
+        };
+        let state = &enrolled.state;
+        disable_totp_service(&enrolled.auth, input, &state.db, &state.totp, &state.passwords, &state.auth_config).await
+    }
+
+    async fn totp_enabled(enrolled: &Enrolled) -> bool {
+        is_totp_enabled_repository(&enrolled.state.db, enrolled.auth.credential_id()).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn disabling_needs_both_the_password_and_a_valid_code(pool: PgPool) {
+        let enrolled = enrolled(pool).await;
+        let valid = code_at(&enrolled.totp, enrolled.confirmed_step + 1);
+
+        rejected(disable(&enrolled, "Wrong-Password-1", &valid).await, "Invalid password");
+        assert_eq!(failed_logins(&enrolled.state).await, 1);
+        assert!(totp_enabled(&enrolled).await);
+
+        rejected(disable(&enrolled, PASSWORD, &wrong_code(&enrolled.totp)).await, "Invalid verification code");
+        assert!(totp_enabled(&enrolled).await);
+
+        let disabled = disable(&enrolled, PASSWORD, &valid).await.unwrap();
+        assert!(!disabled.totp_enabled);
+        assert!(!totp_enabled(&enrolled).await);
+        assert_eq!(
+            recovery_codes_status_service(&enrolled.auth, &enrolled.state.db).await.unwrap().remaining,
+            0
+        );
+    }
+
+    #[sqlx::test]
+    async fn a_recovery_code_works_once(pool: PgPool) {
+        let enrolled = enrolled(pool).await;
+        let code = &enrolled.recovery_codes[0];
+
+        let mfa_token = start_login(&enrolled).await;
+        verify(&enrolled, &mfa_token, code, &enrolled.state.totp).await.unwrap();
+        assert_eq!(
+            recovery_codes_status_service(&enrolled.auth, &enrolled.state.db).await.unwrap().remaining,
+            enrolled.recovery_codes.len() as i64 - 1
+        );
+
+        let mfa_token = start_login(&enrolled).await;
+        rejected(verify(&enrolled, &mfa_token, code, &enrolled.state.totp).await, "Invalid verification code");
+    }
+
+    #[sqlx::test]
+    async fn regenerating_recovery_codes_invalidates_the_previous_set(pool: PgPool) {
+        let enrolled = enrolled(pool).await;
+        let state = &enrolled.state;
+        let code = TotpCodeRequest { code: code_at(&enrolled.totp, enrolled.confirmed_step + 1) };
+
+        let regenerated = regenerate_recovery_codes_service(&enrolled.auth, code, &state.db, &state.totp).await.unwrap();
+        assert!(regenerated.recovery_codes.iter().all(|code| !enrolled.recovery_codes.contains(code)));
+
+        let mfa_token = start_login(&enrolled).await;
+        rejected(
+            verify(&enrolled, &mfa_token, &enrolled.recovery_codes[1], &state.totp).await,
+            "Invalid verification code",
+        );
+        verify(&enrolled, &mfa_token, &regenerated.recovery_codes[1], &state.totp).await.unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jsonwebtoken::get_current_timestamp;
+use qrcode::{render::svg, QrCode};
+use rand::{rngs::OsRng, RngCore};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::mfa::crypto::SecretCipher;
+use crate::config::env::parse_or;
+
+const DIGITS: usize = 6;
+const STEP_SECONDS: u64 = 30;
+// Accept the previous and next 30s window to tolerate clock drift on the phone
+const SKEW_STEPS: u64 = 1;
+const SECRET_BYTES: usize = 20;
+const DEFAULT_MFA_PENDING_TOKEN_TTL_SECONDS: u64 = 5 * 60;
+const DEFAULT_MFA_MAX_ATTEMPTS: i32 = 5;
+
+pub struct TotpConfig {
+    pub cipher: SecretCipher,
+    pub issuer: String,
+    pub mfa_pending_token_ttl_seconds: u64,
+    // Wrong codes a single mfa_token survives before the password step has to be redone
+    pub mfa_max_attempts: i32,
+}
+
+impl TotpConfig {
+    pub fn from_env() -> Self {
+        let issuer = std::env::var("TOTP_ISSUER").unwrap_or_else(|_| "axum_crud".to_string());
+        assert!(!issuer.contains(':'), "TOTP_ISSUER must not contain ':'");
+
+        Self {
+            cipher: SecretCipher::from_env(),
+            issuer,
+            mfa_pending_token_ttl_seconds: parse_or("MFA_PENDING_TOKEN_TTL_SECONDS", DEFAULT_MFA_PENDING_TOKEN_TTL_SECONDS),
+            mfa_max_attempts: parse_or("MFA_MAX_ATTEMPTS", DEFAULT_MFA_MAX_ATTEMPTS),
+        }
+    }
+
+    // RFC 6238 defaults (SHA-1, 6 digits, 30s) since that is what every authenticator app supports
+    pub fn build(&self, secret: Vec<u8>, account_name: &str) -> AppResult<TOTP> {
+        TOTP::new(
+            Algorithm::SHA1,
+            DIGITS,
+            SKEW_STEPS as u8,
+            STEP_SECONDS,
+            secret,
+            Some(self.issuer.clone()),
+            account_name.to_string(),
+        )
+        .map_err(|err| AppError::internal(format!("Invalid TOTP parameters: {err}")))
+    }
+
+    pub fn encrypt_secret(&self, credential_id: i32, secret: &[u8]) -> AppResult<Vec<u8>> {
+        self.cipher.encrypt(secret, &credential_id.to_be_bytes())
+    }
+
+    pub fn decrypt_secret(&self, credential_id: i32, sealed: &[u8]) -> AppResult<Vec<u8>> {
+        self.cipher.decrypt(sealed, &credential_id.to_be_bytes())
+    }
+}
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_BYTES];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+pub fn encoded_secret(secret: &[u8]) -> String {
+    Secret::Raw(secret.to_vec()).to_encoded().to_string()
+}
+
+// Returns the time step the code matched so callers can reject a replay of the same step
+pub fn verify_code(totp: &TOTP, code: &str) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current_step = get_current_timestamp() / STEP_SECONDS;
+    (current_step.saturating_sub(SKEW_STEPS)..=current_step + SKEW_STEPS)
+        .find(|step| constant_time_eq(totp.generate(step * STEP_SECONDS).as_bytes(), code.as_bytes()))
+        .map(|step| step as i64)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn qr_png_base64(uri: &str) -> AppResult<String> {
+    let code = QrCode::new(uri.as_bytes())
+        .map_err(|err| AppError::internal(format!("Failed to build QR code: {err}")))?;
+    let image = code.render::<image::Luma<u8>>().min_dimensions(200, 200).build();
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|err| AppError::internal(format!("Failed to encode QR code: {err}")))?;
+
+    Ok(STANDARD.encode(png))
+}
+
+pub fn qr_svg(uri: &str) -> AppResult<String> {
+    let code = QrCode::new(uri.as_bytes())
+        .map_err(|err| AppError::internal(format!("Failed to build QR code: {err}")))?;
+
+    Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
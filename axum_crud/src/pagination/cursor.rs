@@ -0,0 +1,204 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::config::env::parse_or;
+use crate::crud::error_traits::{AppError, AppResult};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const DEFAULT_MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // Rows after the key
+    Next,
+    // Rows before the key
+    Prev,
+}
+
+// Keyset position for lists ordered by `created_at, id`
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor {
+    pub direction: Direction,
+    pub created_at: DateTime<Utc>,
+    pub id: i32,
+}
+
+// Envelope for every paginated list; cursors are absent at either end
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+// Cursors are `base64url(direction:micros:id).base64url(hmac)`: opaque to clients, and the signature stops
+// them from being forged to jump to arbitrary positions or smuggle in malformed keys
+pub struct PaginationConfig {
+    secret: Vec<u8>,
+    pub default_page_size: i64,
+    pub max_page_size: i64,
+}
+
+impl PaginationConfig {
+    pub fn from_env() -> Self {
+        let secret = std::env::var("PAGINATION_CURSOR_SECRET").expect("PAGINATION_CURSOR_SECRET must be set");
+        let max_page_size = parse_or("PAGINATION_MAX_PAGE_SIZE", DEFAULT_MAX_PAGE_SIZE);
+        assert!(max_page_size >= 1, "PAGINATION_MAX_PAGE_SIZE must be at least 1");
+
+        Self {
+            secret: secret.into_bytes(),
+            default_page_size: DEFAULT_PAGE_SIZE.min(max_page_size),
+            max_page_size,
+        }
+    }
+
+    pub fn page_size(&self, requested: Option<i64>) -> AppResult<i64> {
+        match requested {
+            None => Ok(self.default_page_size),
+            Some(limit) if (1..=self.max_page_size).contains(&limit) => Ok(limit),
+            Some(_) => Err(AppError::validation(format!("limit must be between 1 and {}", self.max_page_size))),
+        }
+    }
+
+    pub fn encode(&self, cursor: &Cursor) -> String {
+        let direction = match cursor.direction {
+            Direction::Next => "n",
+            Direction::Prev => "p",
+        };
+        let payload = format!("{direction}:{}:{}", cursor.created_at.timestamp_micros(), cursor.id);
+        format!("{}.{}", URL_SAFE_NO_PAD.encode(&payload), URL_SAFE_NO_PAD.encode(self.sign(payload.as_bytes())))
+    }
+
+    pub fn decode(&self, token: &str) -> AppResult<Cursor> {
+        self.try_decode(token).ok_or_else(|| AppError::validation("Invalid cursor"))
+    }
+
+    fn try_decode(&self, token: &str) -> Option<Cursor> {
+        let (payload, signature) = token.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature).ok()?;
+
+        let payload = String::from_utf8(payload).ok()?;
+        let mut parts = payload.splitn(3, ':');
+        let direction = match parts.next()? {
+            "n" => Direction::Next,
+            "p" => Direction::Prev,
+            _ => return None,
+        };
+        let created_at = DateTime::from_timestamp_micros(parts.next()?.parse().ok()?)?;
+        let id = parts.next()?.parse().ok()?;
+
+        Some(Cursor {
+            direction,
+            created_at,
+            id,
+        })
+    }
+
+    fn sign(&self, payload: &[u8]) -> Vec<u8> {
+        let mut mac = self.mac();
+        mac.update(payload);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(secret: &str) -> PaginationConfig {
+        PaginationConfig {
+            secret: secret.as_bytes().to_vec(),
+            default_page_size: DEFAULT_PAGE_SIZE,
+            max_page_size: DEFAULT_MAX_PAGE_SIZE,
+        }
+    }
+
+    fn cursor() -> Cursor {
+        Cursor {
+            direction: Direction::Prev,
+            created_at: DateTime::from_timestamp_micros(1_760_700_000_123_456).unwrap(),
+            id: 42,
+        }
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let config = config("cursor-secret");
+
+        for direction in [Direction::Next, Direction::Prev] {
+            let original = Cursor { direction, ..cursor() };
+            let decoded = config.decode(&config.encode(&original)).unwrap();
+
+            assert_eq!(decoded.direction, original.direction);
+            assert_eq!(decoded.created_at, original.created_at);
+            assert_eq!(decoded.id, original.id);
+        }
+    }
+
+    #[test]
+    fn rejects_a_tampered_signature() {
+        let config = config("cursor-secret");
+        let token = config.encode(&cursor());
+        let (payload, signature) = token.split_once('.').unwrap();
+        let flipped = if signature.starts_with('A') { "B" } else { "A" };
+
+        assert!(config.decode(&format!("{payload}.{flipped}{}", &signature[1..])).is_err());
+    }
+
+    #[test]
+    fn rejects_a_tampered_payload() {
+        let config = config("cursor-secret");
+        let token = config.encode(&cursor());
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(format!("p:{}:1", cursor().created_at.timestamp_micros()));
+
+        assert!(config.decode(&format!("{forged}.{signature}")).is_err());
+    }
+
+    #[test]
+    fn rejects_a_cursor_signed_with_another_key() {
+        let token = config("old-secret").encode(&cursor());
+
+        assert!(config("cursor-secret").decode(&token).is_err());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let config = config("cursor-secret");
+        let signed = |payload: &str| {
+            format!("{}.{}", URL_SAFE_NO_PAD.encode(payload), URL_SAFE_NO_PAD.encode(config.sign(payload.as_bytes())))
+        };
+
+        for token in ["", ".", "not-a-cursor", "abc.def", "!!!.???"] {
+            assert!(config.decode(token).is_err(), "{token:?} should not decode");
+        }
+        // Correctly signed, but not a position
+        for payload in ["x:1:2", "n:soon:2", "n:1", "n:1:two", "n:9223372036854775807:1"] {
+            assert!(config.decode(&signed(payload)).is_err(), "{payload:?} should not decode");
+        }
+    }
+
+    #[test]
+    fn page_size_defaults_and_stays_within_the_maximum() {
+        let config = config("cursor-secret");
+
+        assert_eq!(config.page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(config.page_size(Some(DEFAULT_MAX_PAGE_SIZE)).unwrap(), DEFAULT_MAX_PAGE_SIZE);
+        assert!(config.page_size(Some(DEFAULT_MAX_PAGE_SIZE + 1)).is_err());
+        assert!(config.page_size(Some(0)).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use sha1::{Digest, Sha1};
+
+// Offline corpus of known-compromised passwords. Each entry is kept as the first 8 bytes of its SHA-1 in
+// a sorted Vec, so lookups are a binary search; with 64-bit prefixes the chance of a false positive is
+// negligible. That costs 8 bytes per entry, about 80 MB for 10M entries, so the corpus is meant to be a
+// trimmed list of at most a few tens of millions of entries, such as the HIBP hashes seen more than a
+// handful of times. The full HIBP dump (~900M hashes, ~7 GB as prefixes) is too large; filter it first.
+pub struct BreachedPasswords {
+    prefixes: Vec<u64>,
+}
+
+impl BreachedPasswords {
+    // Reads BREACHED_PASSWORDS_FILE; when unset the check is disabled, an unreadable file stops startup
+    pub fn from_env() -> Self {
+        let Ok(path) = std::env::var("BREACHED_PASSWORDS_FILE") else {
+            tracing::warn!("BREACHED_PASSWORDS_FILE is not set, breached-password check disabled");
+            return Self { prefixes: Vec::new() };
+        };
+
+        let corpus = Self::load(&path).unwrap_or_else(|err| panic!("Failed to load breached passwords from {path}: {err}"));
+        tracing::info!(entries = corpus.prefixes.len(), "Loaded breached-password corpus");
+        corpus
+    }
+
+    // Lines are either plaintext passwords or SHA-1 hex digests, optionally followed by `:count`
+    // as in the HIBP downloads; blank lines and `#` comments are skipped
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let mut prefixes = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let entry = line.trim_end_matches(['\r', '\n']);
+            if entry.trim().is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            prefixes.push(hex_digest_prefix(entry).unwrap_or_else(|| password_prefix(entry)));
+        }
+
+        prefixes.sort_unstable();
+        prefixes.dedup();
+        Ok(Self { prefixes })
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        self.prefixes.binary_search(&password_prefix(password)).is_ok()
+    }
+}
+
+fn password_prefix(password: &str) -> u64 {
+    let digest = Sha1::digest(password.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("SHA-1 digest is 20 bytes"))
+}
+
+// `5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8` or `5BAA61E4...:3730471`
+fn hex_digest_prefix(entry: &str) -> Option<u64> {
+    let (digest, count) = entry.split_at_checked(40)?;
+    if !digest.bytes().all(|b| b.is_ascii_hexdigit()) || !(count.is_empty() || count.starts_with(':')) {
+        return None;
+    }
+    u64::from_str_radix(&digest[..16], 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-1 of "password" and "letmein"
+    const PASSWORD_SHA1: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+    const LETMEIN_SHA1: &str = "b7a875fc1ea228b9061041b7cec4bd3c52ab3ce3";
+
+    fn load(name: &str, contents: &str) -> BreachedPasswords {
+        let path = std::env::temp_dir().join(format!("breached-{}-{name}.txt", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let corpus = BreachedPasswords::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        corpus.unwrap()
+    }
+
+    #[test]
+    fn loads_plaintext_passwords() {
+        let corpus = load("plaintext", "Password1\nqwerty\r\n");
+
+        assert!(corpus.contains("Password1"));
+        assert!(corpus.contains("qwerty"));
+        assert!(!corpus.contains("password1"));
+        assert!(!corpus.contains("Correct-Horse-Battery-9"));
+    }
+
+    #[test]
+    fn loads_hibp_digests_with_or_without_counts() {
+        let corpus = load("hibp", &format!("{PASSWORD_SHA1}:3730471\n{LETMEIN_SHA1}\n"));
+
+        assert_eq!(corpus.prefixes.len(), 2);
+        assert!(corpus.contains("password"));
+        assert!(corpus.contains("letmein"));
+        // The digest is matched, not the line it came from
+        assert!(!corpus.contains(PASSWORD_SHA1));
+    }
+
+    #[test]
+    fn treats_lines_that_only_look_like_digests_as_passwords() {
+        let almost = format!("{PASSWORD_SHA1}x");
+        let corpus = load("almost", &format!("{almost}\n{}\n", &PASSWORD_SHA1[..39]));
+
+        assert!(corpus.contains(&almost));
+        assert!(corpus.contains(&PASSWORD_SHA1[..39]));
+        assert!(!corpus.contains("password"));
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let corpus = load("comments", "# starter list\n\n   \nhunter2\n#letmein\n\n");
+
+        assert_eq!(corpus.prefixes.len(), 1);
+        assert!(corpus.contains("hunter2"));
+        assert!(!corpus.contains("letmein"));
+        assert!(!corpus.contains("# starter list"));
+        assert!(!corpus.contains(""));
+    }
+
+    #[test]
+    fn keeps_one_entry_per_password() {
+        let corpus = load("duplicates", &format!("password\n{PASSWORD_SHA1}:12\npassword\n"));
+
+        assert_eq!(corpus.prefixes.len(), 1);
+        assert!(corpus.contains("password"));
+    }
+
+    #[test]
+    fn fails_when_the_file_is_missing() {
+        assert!(BreachedPasswords::load("/nonexistent/breached_passwords.txt").is_err());
+    }
+}
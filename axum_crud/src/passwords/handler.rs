@@ -0,0 +1,18 @@
+use axum::{
+    extract::State,
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::rbac::extractor::RequirePermission;
+use crate::rbac::permissions::MetricsRead;
+
+#[axum::debug_handler]
+pub async fn hashing_metrics_handler(
+    State(state): State<AppState>,
+    _guard: RequirePermission<MetricsRead>,
+) -> AppResult<impl IntoResponse> {
+    Ok((StatusCode::OK, Json(state.passwords.metrics())))
+}
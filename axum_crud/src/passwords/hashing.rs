@@ -0,0 +1,219 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use rand::rngs::OsRng;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::passwords::legacy::LegacyScheme;
+use crate::config::env::parse_or;
+
+// OWASP's baseline Argon2id parameters
+const DEFAULT_ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+// How a native hash was produced, recognised from its prefix
+enum StoredScheme {
+    Argon2,
+    Bcrypt,
+}
+
+// New hashes use the configured algorithm as a PHC string (`$argon2id$v=19$m=..,t=..,p=..$salt$hash`);
+// older bcrypt rows (`$2b$12$...`) and imported hashes tagged with a `LegacyScheme` still verify,
+// and `needs_rehash` upgrades them on the next login.
+pub struct PasswordHasher {
+    algorithm: HashAlgorithm,
+    argon2_params: Params,
+    bcrypt_cost: u32,
+}
+
+impl PasswordHasher {
+    pub fn from_env() -> Self {
+        let algorithm = match std::env::var("PASSWORD_HASH_ALGORITHM").as_deref() {
+            Ok("argon2id") | Err(_) => HashAlgorithm::Argon2id,
+            Ok("bcrypt") => HashAlgorithm::Bcrypt,
+            Ok(other) => panic!("Unsupported PASSWORD_HASH_ALGORITHM: {other}"),
+        };
+
+        let argon2_params = Params::new(
+            parse_or("ARGON2_MEMORY_KIB", DEFAULT_ARGON2_MEMORY_KIB),
+            parse_or("ARGON2_ITERATIONS", DEFAULT_ARGON2_ITERATIONS),
+            parse_or("ARGON2_PARALLELISM", DEFAULT_ARGON2_PARALLELISM),
+            None,
+        )
+        .unwrap_or_else(|err| panic!("Invalid Argon2 parameters: {err}"));
+
+        let bcrypt_cost = parse_or("BCRYPT_COST", bcrypt::DEFAULT_COST);
+        assert!((4..=31).contains(&bcrypt_cost), "BCRYPT_COST must be between 4 and 31");
+
+        Self {
+            algorithm,
+            argon2_params,
+            bcrypt_cost,
+        }
+    }
+
+    pub fn hash(&self, password: &str) -> AppResult<String> {
+        match self.algorithm {
+            HashAlgorithm::Argon2id => {
+                let salt = SaltString::generate(&mut OsRng);
+                Ok(self.argon2().hash_password(password.as_bytes(), &salt)?.to_string())
+            }
+            HashAlgorithm::Bcrypt => Ok(bcrypt::hash(password, self.bcrypt_cost)?),
+        }
+    }
+
+    pub fn verify(&self, password: &str, stored_hash: &str, legacy_scheme: Option<&str>) -> AppResult<bool> {
+        if let Some(legacy_scheme) = legacy_scheme {
+            // A stored hash that doesn't parse is a server-side problem, not the caller's
+            return legacy_scheme
+                .parse::<LegacyScheme>()
+                .and_then(|legacy_scheme| legacy_scheme.verify(password, stored_hash))
+                .map_err(|err| AppError::PasswordHashing(err.to_string()));
+        }
+
+        match native_scheme(stored_hash)? {
+            StoredScheme::Argon2 => {
+                let parsed = PasswordHash::new(stored_hash)?;
+                // Parameters come from the stored string, not the current configuration
+                match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+                    Ok(()) => Ok(true),
+                    Err(argon2::password_hash::Error::Password) => Ok(false),
+                    Err(err) => Err(err.into()),
+                }
+            }
+            StoredScheme::Bcrypt => Ok(bcrypt::verify(password, stored_hash)?),
+        }
+    }
+
+    // True when the hash was made with another algorithm or other parameters than currently configured
+    pub fn needs_rehash(&self, stored_hash: &str, legacy_scheme: Option<&str>) -> bool {
+        if legacy_scheme.is_some() {
+            return true;
+        }
+
+        match (self.algorithm, native_scheme(stored_hash)) {
+            (HashAlgorithm::Argon2id, Ok(StoredScheme::Argon2)) => {
+                let Ok(parsed) = PasswordHash::new(stored_hash) else {
+                    return true;
+                };
+                let current = &self.argon2_params;
+                parsed.algorithm != Algorithm::Argon2id.ident()
+                    || parsed.version != Some(Version::V0x13.into())
+                    || Params::try_from(&parsed).map_or(true, |params| {
+                        params.m_cost() != current.m_cost()
+                            || params.t_cost() != current.t_cost()
+                            || params.p_cost() != current.p_cost()
+                    })
+            }
+            (HashAlgorithm::Bcrypt, Ok(StoredScheme::Bcrypt)) => bcrypt_cost(stored_hash) != Some(self.bcrypt_cost),
+            _ => true,
+        }
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.argon2_params.clone())
+    }
+}
+
+fn native_scheme(stored_hash: &str) -> AppResult<StoredScheme> {
+    if stored_hash.starts_with("$argon2") {
+        Ok(StoredScheme::Argon2)
+    } else if ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| stored_hash.starts_with(prefix)) {
+        Ok(StoredScheme::Bcrypt)
+    } else {
+        Err(AppError::PasswordHashing("Unrecognised password hash format".to_string()))
+    }
+}
+
+// `$2b$12$...` -> 12
+fn bcrypt_cost(stored_hash: &str) -> Option<u32> {
+    stored_hash.split('$').nth(2)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small enough to keep the tests fast, the checks only compare parameters
+    fn argon2_hasher(m_cost: u32, t_cost: u32, p_cost: u32) -> PasswordHasher {
+        PasswordHasher {
+            algorithm: HashAlgorithm::Argon2id,
+            argon2_params: Params::new(m_cost, t_cost, p_cost, None).unwrap(),
+            bcrypt_cost: 4,
+        }
+    }
+
+    fn bcrypt_hasher(cost: u32) -> PasswordHasher {
+        PasswordHasher {
+            algorithm: HashAlgorithm::Bcrypt,
+            argon2_params: Params::new(1024, 1, 1, None).unwrap(),
+            bcrypt_cost: cost,
+        }
+    }
+
+    fn argon2_hash(algorithm: Algorithm, version: Version, m_cost: u32, t_cost: u32, p_cost: u32) -> String {
+        let params = Params::new(m_cost, t_cost, p_cost, None).unwrap();
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::new(algorithm, version, params).hash_password(b"password", &salt).unwrap().to_string()
+    }
+
+    #[test]
+    fn keeps_hashes_made_with_the_current_settings() {
+        let hasher = argon2_hasher(1024, 1, 1);
+        assert!(!hasher.needs_rehash(&hasher.hash("password").unwrap(), None));
+
+        let hasher = bcrypt_hasher(5);
+        assert!(!hasher.needs_rehash(&hasher.hash("password").unwrap(), None));
+    }
+
+    #[test]
+    fn upgrades_argon2_hashes_with_other_parameters_or_variants() {
+        let hasher = argon2_hasher(1024, 2, 1);
+        for stale in [
+            argon2_hash(Algorithm::Argon2id, Version::V0x13, 512, 2, 1),
+            argon2_hash(Algorithm::Argon2id, Version::V0x13, 1024, 1, 1),
+            argon2_hash(Algorithm::Argon2id, Version::V0x13, 1024, 2, 2),
+            argon2_hash(Algorithm::Argon2i, Version::V0x13, 1024, 2, 1),
+            argon2_hash(Algorithm::Argon2d, Version::V0x13, 1024, 2, 1),
+            argon2_hash(Algorithm::Argon2id, Version::V0x10, 1024, 2, 1),
+        ] {
+            assert!(hasher.needs_rehash(&stale, None), "{stale} should be upgraded");
+            assert!(hasher.verify("password", &stale, None).unwrap());
+        }
+    }
+
+    #[test]
+    fn upgrades_bcrypt_hashes_to_argon2id() {
+        let hasher = argon2_hasher(1024, 1, 1);
+        let bcrypt_hash = bcrypt::hash("password", 4).unwrap();
+        assert!(hasher.needs_rehash(&bcrypt_hash, None));
+        assert!(hasher.verify("password", &bcrypt_hash, None).unwrap());
+    }
+
+    #[test]
+    fn upgrades_bcrypt_hashes_with_another_cost() {
+        let hasher = bcrypt_hasher(5);
+        assert!(hasher.needs_rehash(&bcrypt::hash("password", 4).unwrap(), None));
+        assert!(hasher.needs_rehash(&bcrypt::hash("password", 6).unwrap(), None));
+        assert!(hasher.needs_rehash(&argon2_hash(Algorithm::Argon2id, Version::V0x13, 1024, 1, 1), None));
+    }
+
+    #[test]
+    fn upgrades_legacy_and_unrecognised_hashes() {
+        let hasher = argon2_hasher(1024, 1, 1);
+        let current = hasher.hash("password").unwrap();
+        // The scheme tag wins even when the string itself looks current
+        assert!(hasher.needs_rehash(&current, Some("django")));
+        assert!(hasher.needs_rehash("sha1$pepper$73614fd51a90257f32acee922225eb8a815b89c8", Some("salted_sha1")));
+        assert!(hasher.needs_rehash("5f4dcc3b5aa765d61d8327deb882cf99", None));
+        assert!(hasher.needs_rehash("$argon2id$v=19$garbage", None));
+    }
+}
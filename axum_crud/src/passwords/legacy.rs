@@ -0,0 +1,312 @@
+use std::str::FromStr;
+
+use argon2::password_hash::{PasswordHash, PasswordVerifier as _};
+use base64::{engine::general_purpose::{STANDARD, STANDARD_NO_PAD}, Engine};
+use pbkdf2::pbkdf2_hmac;
+use scrypt::Scrypt;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::crud::error_traits::{AppError, AppResult};
+
+// Guards against imported hashes that would make a single login arbitrarily expensive
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+const MAX_SCRYPT_R_TIMES_P: u32 = 64;
+// scrypt allocates 128·r·2^ln bytes per attempt
+const MAX_SCRYPT_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+// PBKDF2 pays the full iteration count again for every digest-sized block of output,
+// so stored hashes are held to a single block
+const PBKDF2_SHA256_HASH_BYTES: usize = 32;
+const PBKDF2_SHA1_HASH_BYTES: usize = 20;
+
+// Hash formats accepted from other systems, stored as-is and tagged in credentials.password_scheme:
+//   pbkdf2_sha256  passlib's `$pbkdf2-sha256$<iterations>$<salt>$<hash>` (adapted base64, `.` for `+`)
+//   scrypt         PHC `$scrypt$ln=<log2 n>,r=<r>,p=<p>$<salt>$<hash>`
+//   django         `pbkdf2_sha256$<iterations>$<salt>$<base64 hash>` (or pbkdf2_sha1)
+//   salted_sha1    `sha1$<salt>$<hex sha1(salt + password)>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyScheme {
+    Pbkdf2Sha256,
+    Scrypt,
+    Django,
+    SaltedSha1,
+}
+
+impl LegacyScheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LegacyScheme::Pbkdf2Sha256 => "pbkdf2_sha256",
+            LegacyScheme::Scrypt => "scrypt",
+            LegacyScheme::Django => "django",
+            LegacyScheme::SaltedSha1 => "salted_sha1",
+        }
+    }
+
+    // Checks that `hash` is well formed for this scheme without needing the password
+    pub fn validate(self, hash: &str) -> AppResult<()> {
+        match self {
+            LegacyScheme::Pbkdf2Sha256 => parse_passlib_pbkdf2(hash).map(|_| ()),
+            LegacyScheme::Scrypt => parse_scrypt(hash).map(|_| ()),
+            LegacyScheme::Django => parse_django(hash).map(|_| ()),
+            LegacyScheme::SaltedSha1 => parse_salted_sha1(hash).map(|_| ()),
+        }
+    }
+
+    pub fn verify(self, password: &str, hash: &str) -> AppResult<bool> {
+        let password = password.as_bytes();
+        match self {
+            LegacyScheme::Pbkdf2Sha256 => {
+                let (iterations, salt, expected) = parse_passlib_pbkdf2(hash)?;
+                let mut derived = vec![0u8; expected.len()];
+                pbkdf2_hmac::<Sha256>(password, &salt, iterations, &mut derived);
+                Ok(constant_time_eq(&derived, &expected))
+            }
+            LegacyScheme::Scrypt => {
+                let parsed = parse_scrypt(hash)?;
+                match Scrypt.verify_password(password, &parsed) {
+                    Ok(()) => Ok(true),
+                    Err(argon2::password_hash::Error::Password) => Ok(false),
+                    Err(err) => Err(err.into()),
+                }
+            }
+            LegacyScheme::Django => {
+                let (algorithm, iterations, salt, expected) = parse_django(hash)?;
+                let mut derived = vec![0u8; expected.len()];
+                match algorithm {
+                    DjangoAlgorithm::Pbkdf2Sha256 => pbkdf2_hmac::<Sha256>(password, salt.as_bytes(), iterations, &mut derived),
+                    DjangoAlgorithm::Pbkdf2Sha1 => pbkdf2_hmac::<Sha1>(password, salt.as_bytes(), iterations, &mut derived),
+                }
+                Ok(constant_time_eq(&derived, &expected))
+            }
+            LegacyScheme::SaltedSha1 => {
+                let (salt, expected) = parse_salted_sha1(hash)?;
+                let derived = Sha1::new().chain_update(salt.as_bytes()).chain_update(password).finalize();
+                Ok(constant_time_eq(&derived, &expected))
+            }
+        }
+    }
+}
+
+impl FromStr for LegacyScheme {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pbkdf2_sha256" => Ok(LegacyScheme::Pbkdf2Sha256),
+            "scrypt" => Ok(LegacyScheme::Scrypt),
+            "django" => Ok(LegacyScheme::Django),
+            "salted_sha1" => Ok(LegacyScheme::SaltedSha1),
+            other => Err(AppError::validation(format!("Unsupported password scheme: {other}"))),
+        }
+    }
+}
+
+enum DjangoAlgorithm {
+    Pbkdf2Sha256,
+    Pbkdf2Sha1,
+}
+
+impl DjangoAlgorithm {
+    fn max_hash_bytes(&self) -> usize {
+        match self {
+            DjangoAlgorithm::Pbkdf2Sha256 => PBKDF2_SHA256_HASH_BYTES,
+            DjangoAlgorithm::Pbkdf2Sha1 => PBKDF2_SHA1_HASH_BYTES,
+        }
+    }
+}
+
+fn malformed(scheme: LegacyScheme) -> AppError {
+    AppError::validation(format!("Malformed {} hash", scheme.as_str()))
+}
+
+fn constant_time_eq(derived: &[u8], expected: &[u8]) -> bool {
+    derived.len() == expected.len() && bool::from(derived.ct_eq(expected))
+}
+
+fn parse_iterations(value: &str, scheme: LegacyScheme) -> AppResult<u32> {
+    value
+        .parse()
+        .ok()
+        .filter(|iterations| (1..=MAX_PBKDF2_ITERATIONS).contains(iterations))
+        .ok_or_else(|| malformed(scheme))
+}
+
+// passlib's "adapted base64": standard alphabet with `.` instead of `+`, unpadded
+fn decode_ab64(value: &str) -> Option<Vec<u8>> {
+    STANDARD_NO_PAD.decode(value.replace('.', "+")).ok()
+}
+
+fn parse_passlib_pbkdf2(hash: &str) -> AppResult<(u32, Vec<u8>, Vec<u8>)> {
+    let scheme = LegacyScheme::Pbkdf2Sha256;
+    let mut parts = hash.strip_prefix("$pbkdf2-sha256$").ok_or_else(|| malformed(scheme))?.split('$');
+    let (Some(iterations), Some(salt), Some(expected), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(malformed(scheme));
+    };
+
+    let iterations = parse_iterations(iterations, scheme)?;
+    let salt = decode_ab64(salt).ok_or_else(|| malformed(scheme))?;
+    let expected = decode_ab64(expected)
+        .filter(|bytes| (1..=PBKDF2_SHA256_HASH_BYTES).contains(&bytes.len()))
+        .ok_or_else(|| malformed(scheme))?;
+    Ok((iterations, salt, expected))
+}
+
+fn parse_scrypt(hash: &str) -> AppResult<PasswordHash<'_>> {
+    let scheme = LegacyScheme::Scrypt;
+    let parsed = PasswordHash::new(hash).map_err(|_| malformed(scheme))?;
+    if parsed.algorithm.as_str() != "scrypt" || parsed.hash.is_none() {
+        return Err(malformed(scheme));
+    }
+    let log_n = parsed.params.get_decimal("ln").ok_or_else(|| malformed(scheme))?;
+    // Missing r and p fall back to the same defaults scrypt uses when verifying
+    let r = parsed.params.get_decimal("r").unwrap_or(scrypt::Params::RECOMMENDED_R);
+    let p = parsed.params.get_decimal("p").unwrap_or(scrypt::Params::RECOMMENDED_P);
+    if log_n > u32::from(MAX_SCRYPT_LOG_N) || r > MAX_SCRYPT_R || p > MAX_SCRYPT_P || r * p > MAX_SCRYPT_R_TIMES_P {
+        return Err(malformed(scheme));
+    }
+    if 128 * u64::from(r) * (1_u64 << log_n) > MAX_SCRYPT_MEMORY_BYTES {
+        return Err(malformed(scheme));
+    }
+    Ok(parsed)
+}
+
+fn parse_django(hash: &str) -> AppResult<(DjangoAlgorithm, u32, &str, Vec<u8>)> {
+    let scheme = LegacyScheme::Django;
+    let mut parts = hash.split('$');
+    let (Some(algorithm), Some(iterations), Some(salt), Some(expected), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(malformed(scheme));
+    };
+
+    let algorithm = match algorithm {
+        "pbkdf2_sha256" => DjangoAlgorithm::Pbkdf2Sha256,
+        "pbkdf2_sha1" => DjangoAlgorithm::Pbkdf2Sha1,
+        _ => return Err(malformed(scheme)),
+    };
+    let iterations = parse_iterations(iterations, scheme)?;
+    let expected = STANDARD
+        .decode(expected)
+        .ok()
+        .filter(|bytes| (1..=algorithm.max_hash_bytes()).contains(&bytes.len()))
+        .ok_or_else(|| malformed(scheme))?;
+    Ok((algorithm, iterations, salt, expected))
+}
+
+fn parse_salted_sha1(hash: &str) -> AppResult<(&str, Vec<u8>)> {
+    let scheme = LegacyScheme::SaltedSha1;
+    let mut parts = hash.split('$');
+    let (Some("sha1"), Some(salt), Some(expected), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(malformed(scheme));
+    };
+
+    let expected = hex::decode(expected)
+        .ok()
+        .filter(|bytes| bytes.len() == 20)
+        .ok_or_else(|| malformed(scheme))?;
+    Ok((salt, expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Generated with Python's hashlib for the password "password"
+    const PASSLIB_PBKDF2: &str =
+        "$pbkdf2-sha256$1000$c2FsdDAwMDBzYWx0MDAwMA$cEn29jp1JovEMP4XQfoLdXYH65qZ2LG/5O9.85V2IEI";
+    const SCRYPT: &str = "$scrypt$ln=10,r=8,p=1$c2FsdHNhbHRzYWx0c2FsdA$BVMRKqdiVYikKAaPR1wucsKUKvw4TuPLkdEYtoSHas4";
+    const DJANGO_SHA256: &str = "pbkdf2_sha256$1000$seasalt$YIWkt6M1JFXrHg5s0jZjBSc7C2Cz6QvchSJ0h8Y+i7c=";
+    const DJANGO_SHA1: &str = "pbkdf2_sha1$1000$seasalt$C8KvRfPW529R7JpDHEDOP35Xr0g=";
+    const SALTED_SHA1: &str = "sha1$pepper$73614fd51a90257f32acee922225eb8a815b89c8";
+
+    fn vectors() -> [(LegacyScheme, &'static str); 5] {
+        [
+            (LegacyScheme::Pbkdf2Sha256, PASSLIB_PBKDF2),
+            (LegacyScheme::Scrypt, SCRYPT),
+            (LegacyScheme::Django, DJANGO_SHA256),
+            (LegacyScheme::Django, DJANGO_SHA1),
+            (LegacyScheme::SaltedSha1, SALTED_SHA1),
+        ]
+    }
+
+    fn assert_malformed(scheme: LegacyScheme, hash: &str) {
+        assert!(scheme.validate(hash).is_err(), "{hash} should not validate");
+        assert!(scheme.verify("password", hash).is_err(), "{hash} should not verify");
+    }
+
+    #[test]
+    fn verifies_known_answer_vectors() {
+        for (scheme, hash) in vectors() {
+            scheme.validate(hash).unwrap();
+            assert!(scheme.verify("password", hash).unwrap(), "{hash} should match");
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_passwords() {
+        for (scheme, hash) in vectors() {
+            assert!(!scheme.verify("Password", hash).unwrap(), "{hash} should not match");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_hashes() {
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, "");
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, "$pbkdf2-sha256$1000$c2FsdA");
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, "$pbkdf2-sha256$abc$c2FsdA$c2FsdA");
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, "$pbkdf2-sha256$1000$c2FsdA$$");
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, DJANGO_SHA256);
+        assert_malformed(LegacyScheme::Scrypt, "$scrypt$r=8,p=1$c2FsdHNhbHRzYWx0c2FsdA$BVMRKqdiVYikKAaPR1wucsKUKvw4TuPLkdEYtoSHas4");
+        assert_malformed(LegacyScheme::Scrypt, "$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$c2FsdHNhbHQ");
+        assert_malformed(LegacyScheme::Scrypt, "$scrypt$ln=10,r=8,p=1$c2FsdHNhbHRzYWx0c2FsdA");
+        assert_malformed(LegacyScheme::Django, "md5$seasalt$abc");
+        assert_malformed(LegacyScheme::Django, "pbkdf2_sha256$1000$seasalt$not base64!");
+        assert_malformed(LegacyScheme::Django, "pbkdf2_sha256$0$seasalt$YIWkt6M1JFXrHg5s0jZjBSc7C2Cz6QvchSJ0h8Y+i7c=");
+        assert_malformed(LegacyScheme::SaltedSha1, "sha1$pepper$abcd");
+        assert_malformed(LegacyScheme::SaltedSha1, "md5$pepper$73614fd51a90257f32acee922225eb8a815b89c8");
+        assert_malformed(LegacyScheme::SaltedSha1, "sha1$pepper$73614fd51a90257f32acee922225eb8a815b89c8$extra");
+    }
+
+    #[test]
+    fn rejects_iteration_counts_above_the_cap() {
+        let over = MAX_PBKDF2_ITERATIONS + 1;
+        assert_malformed(
+            LegacyScheme::Pbkdf2Sha256,
+            &PASSLIB_PBKDF2.replace("$1000$", &format!("${over}$")),
+        );
+        assert_malformed(LegacyScheme::Django, &DJANGO_SHA256.replace("$1000$", &format!("${over}$")));
+        assert_malformed(LegacyScheme::Django, &DJANGO_SHA1.replace("$1000$", &format!("${over}$")));
+    }
+
+    #[test]
+    fn rejects_scrypt_costs_above_the_caps() {
+        let with_params = |params: &str| SCRYPT.replace("ln=10,r=8,p=1", params);
+
+        assert_malformed(LegacyScheme::Scrypt, &with_params(&format!("ln={},r=1,p=1", MAX_SCRYPT_LOG_N + 1)));
+        assert_malformed(LegacyScheme::Scrypt, &with_params("ln=20,r=1024,p=1"));
+        assert_malformed(LegacyScheme::Scrypt, &with_params(&format!("ln=4,r={},p=1", MAX_SCRYPT_R + 1)));
+        assert_malformed(LegacyScheme::Scrypt, &with_params(&format!("ln=4,r=1,p={}", MAX_SCRYPT_P + 1)));
+        assert_malformed(LegacyScheme::Scrypt, &with_params("ln=4,r=16,p=16"));
+        // 128 · 8 · 2^17 bytes is 128 MiB, over the memory cap even though each parameter is in range
+        assert_malformed(LegacyScheme::Scrypt, &with_params("ln=17,r=8,p=1"));
+        assert_malformed(LegacyScheme::Scrypt, &with_params("ln=17"));
+    }
+
+    #[test]
+    fn rejects_pbkdf2_hashes_longer_than_one_block() {
+        let passlib = |bytes: usize| format!("$pbkdf2-sha256$1000$c2FsdA${}", STANDARD_NO_PAD.encode(vec![0u8; bytes]));
+        let django = |algorithm: &str, bytes: usize| format!("{algorithm}$1000$seasalt${}", STANDARD.encode(vec![0u8; bytes]));
+
+        LegacyScheme::Pbkdf2Sha256.validate(&passlib(PBKDF2_SHA256_HASH_BYTES)).unwrap();
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, &passlib(PBKDF2_SHA256_HASH_BYTES + 1));
+        assert_malformed(LegacyScheme::Pbkdf2Sha256, &passlib(1 << 20));
+        LegacyScheme::Django.validate(&django("pbkdf2_sha256", PBKDF2_SHA256_HASH_BYTES)).unwrap();
+        assert_malformed(LegacyScheme::Django, &django("pbkdf2_sha256", PBKDF2_SHA256_HASH_BYTES + 1));
+        LegacyScheme::Django.validate(&django("pbkdf2_sha1", PBKDF2_SHA1_HASH_BYTES)).unwrap();
+        assert_malformed(LegacyScheme::Django, &django("pbkdf2_sha1", PBKDF2_SHA1_HASH_BYTES + 1));
+    }
+}
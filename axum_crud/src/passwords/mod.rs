@@ -0,0 +1,10 @@
+pub mod hashing;
+pub mod legacy;
+pub mod breached;
+pub mod policy;
+pub mod pool;
+pub mod model;
+pub mod repository;
+pub mod services;
+pub mod handler;
+pub mod routes;
@@ -0,0 +1,5 @@
+// A previously set password, verified the same way as the current one
+pub struct PasswordHistoryEntry {
+    pub password_hash: String,
+    pub password_scheme: Option<String>,
+}
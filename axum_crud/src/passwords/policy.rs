@@ -0,0 +1,194 @@
+use serde::Serialize;
+use zxcvbn::zxcvbn;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::passwords::breached::BreachedPasswords;
+use crate::config::env::{bool_or, parse_or};
+
+const DEFAULT_MIN_LENGTH: usize = 8;
+const DEFAULT_MAX_LENGTH: usize = 128;
+// zxcvbn scores run 0-4, where 2 means roughly 10^8 guesses
+const DEFAULT_MIN_STRENGTH: u8 = 2;
+const MAX_STRENGTH: u8 = 4;
+// Shorter local parts (`a@b.com`) would ban nearly every password
+const MIN_BANNED_EMAIL_PART_LENGTH: usize = 3;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PolicyViolation {
+    // Stable code the frontend can key messages on, e.g. `min_length`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct PasswordEvaluation {
+    pub acceptable: bool,
+    // zxcvbn score, 0 (guessable in ~10^3 tries) to 4 (more than 10^10)
+    pub score: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    pub violations: Vec<PolicyViolation>,
+}
+
+// Rules every new password must satisfy, read once at startup. Unlike a first-failure check it reports
+// every violated rule, so the same evaluation drives registration errors and live strength feedback.
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    require_uppercase: bool,
+    require_lowercase: bool,
+    require_digit: bool,
+    require_symbol: bool,
+    // Lowercased; matched as substrings, case-insensitively
+    banned_words: Vec<String>,
+    min_strength: u8,
+    breached: BreachedPasswords,
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let min_length = parse_or("PASSWORD_MIN_LENGTH", DEFAULT_MIN_LENGTH);
+        let max_length = parse_or("PASSWORD_MAX_LENGTH", DEFAULT_MAX_LENGTH);
+        assert!(min_length >= 1, "PASSWORD_MIN_LENGTH must be at least 1");
+        assert!(max_length >= min_length, "PASSWORD_MAX_LENGTH must not be below PASSWORD_MIN_LENGTH");
+
+        let min_strength = parse_or("PASSWORD_MIN_STRENGTH", DEFAULT_MIN_STRENGTH as usize);
+        assert!(min_strength <= MAX_STRENGTH as usize, "PASSWORD_MIN_STRENGTH must be between 0 and {MAX_STRENGTH}");
+
+        let banned_words = std::env::var("PASSWORD_BANNED_WORDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        Self {
+            min_length,
+            max_length,
+            require_uppercase: bool_or("PASSWORD_REQUIRE_UPPERCASE", true),
+            require_lowercase: bool_or("PASSWORD_REQUIRE_LOWERCASE", true),
+            require_digit: bool_or("PASSWORD_REQUIRE_DIGIT", true),
+            require_symbol: bool_or("PASSWORD_REQUIRE_SYMBOL", false),
+            banned_words,
+            min_strength: min_strength as u8,
+            breached: BreachedPasswords::from_env(),
+        }
+    }
+
+    // `email` is the account the password is for, when known; its local part is banned and
+    // also feeds the strength estimate
+    pub fn evaluate(&self, password: &str, email: Option<&str>) -> PasswordEvaluation {
+        // zxcvbn's cost grows quickly with length, so oversized input is turned away before any of the
+        // other checks run on it
+        if password.chars().nth(self.max_length).is_some() {
+            return PasswordEvaluation {
+                acceptable: false,
+                score: 0,
+                warning: None,
+                suggestions: Vec::new(),
+                violations: vec![PolicyViolation {
+                    rule: "max_length",
+                    message: format!("Password must be at most {} characters long", self.max_length),
+                }],
+            };
+        }
+
+        let mut violations = Vec::new();
+        let mut violate = |rule, message: String| violations.push(PolicyViolation { rule, message });
+
+        if password.chars().count() < self.min_length {
+            violate("min_length", format!("Password must be at least {} characters long", self.min_length));
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violate("uppercase", "Password must contain at least one uppercase letter".to_string());
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violate("lowercase", "Password must contain at least one lowercase letter".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violate("digit", "Password must contain at least one digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            violate("symbol", "Password must contain at least one symbol".to_string());
+        }
+
+        let lowercase = password.to_lowercase();
+        for word in &self.banned_words {
+            if lowercase.contains(word.as_str()) {
+                violate("banned_word", format!("Password must not contain \"{word}\""));
+            }
+        }
+
+        let email = email.map(|email| email.trim().to_lowercase());
+        let local_part = email.as_deref().and_then(|email| email.split('@').next()).unwrap_or_default();
+        if local_part.chars().count() >= MIN_BANNED_EMAIL_PART_LENGTH && lowercase.contains(local_part) {
+            violate("email", "Password must not contain your email address".to_string());
+        }
+
+        if self.breached.contains(password) {
+            violate("breached", "This password has appeared in a data breach, choose a different one".to_string());
+        }
+
+        let user_inputs: Vec<&str> = email.as_deref().into_iter().chain([local_part]).filter(|input| !input.is_empty()).collect();
+        let entropy = zxcvbn(password, &user_inputs);
+        let score = u8::from(entropy.score());
+        if score < self.min_strength {
+            violate("strength", "Password is too easy to guess".to_string());
+        }
+
+        let feedback = entropy.feedback();
+        PasswordEvaluation {
+            acceptable: violations.is_empty(),
+            score,
+            warning: feedback.and_then(|feedback| feedback.warning()).map(|warning| warning.to_string()),
+            suggestions: feedback
+                .map(|feedback| feedback.suggestions().iter().map(ToString::to_string).collect())
+                .unwrap_or_default(),
+            violations,
+        }
+    }
+
+    pub fn check(&self, password: &str, email: Option<&str>) -> AppResult<()> {
+        let evaluation = self.evaluate(password, email);
+        if evaluation.acceptable {
+            Ok(())
+        } else {
+            Err(AppError::PasswordPolicy {
+                violations: evaluation.violations,
+            })
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::load_env;
+
+    #[test]
+    fn rejects_oversized_passwords_on_length_alone() {
+        load_env();
+        let policy = PasswordPolicy::from_env();
+        let password = "password".repeat(100_000);
+
+        let evaluation = policy.evaluate(&password, Some("someone@example.com"));
+
+        assert!(!evaluation.acceptable);
+        assert_eq!(evaluation.score, 0);
+        let rules: Vec<_> = evaluation.violations.iter().map(|violation| violation.rule).collect();
+        assert_eq!(rules, ["max_length"]);
+    }
+
+    #[test]
+    fn still_evaluates_passwords_at_the_maximum_length() {
+        load_env();
+        let policy = PasswordPolicy::from_env();
+        let password = "Aa1".repeat(policy.max_length / 3);
+
+        let evaluation = policy.evaluate(&password, None);
+
+        assert!(evaluation.violations.iter().all(|violation| violation.rule != "max_length"));
+    }
+}
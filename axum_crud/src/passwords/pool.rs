@@ -0,0 +1,330 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::passwords::hashing::PasswordHasher;
+use crate::config::env::parse_or;
+
+const DEFAULT_QUEUE_LIMIT: usize = 32;
+const DEFAULT_RETRY_AFTER_SECONDS: u64 = 1;
+
+// Runs hashing and verification on Tokio's blocking threads, at most `workers` at a time, so a burst of
+// signups can't starve the async workers serving every other route. Jobs past the workers wait in a
+// queue of at most `queue_limit`; beyond that requests are shed with 503 and Retry-After.
+pub struct HashingPool {
+    hasher: Arc<PasswordHasher>,
+    workers: Arc<Semaphore>,
+    worker_count: usize,
+    queue_limit: usize,
+    retry_after_seconds: u64,
+    metrics: Arc<HashingMetrics>,
+    // Hash of a throwaway password with the current settings, see `verify_dummy`
+    dummy_hash: String,
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Hash,
+    Verify,
+}
+
+#[derive(Default)]
+struct HashingMetrics {
+    // Admitted jobs, queued or running
+    pending: AtomicUsize,
+    running: AtomicUsize,
+    rejected: AtomicU64,
+    queue_wait: LatencyStats,
+    hash: LatencyStats,
+    verify: LatencyStats,
+}
+
+#[derive(Default)]
+struct LatencyStats {
+    count: AtomicU64,
+    total_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct HashingMetricsSnapshot {
+    pub workers: usize,
+    pub queue_limit: usize,
+    pub queue_depth: usize,
+    pub in_flight: usize,
+    pub rejected: u64,
+    pub queue_wait: LatencySnapshot,
+    pub hash: LatencySnapshot,
+    pub verify: LatencySnapshot,
+}
+
+#[derive(Serialize)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub average_ms: f64,
+    pub max_ms: f64,
+}
+
+// Holds the job's queue slot until the job itself is done. The request's future owns it while waiting for a
+// worker, so a request dropped in the queue frees its slot; once spawned it moves into the blocking job,
+// which keeps running even if the request is dropped.
+struct Admission(Arc<HashingMetrics>);
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl HashingPool {
+    // HASH_WORKERS defaults to the number of CPUs
+    pub fn from_env(hasher: PasswordHasher) -> Self {
+        let default_workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let worker_count = parse_or("HASH_WORKERS", default_workers);
+        assert!(worker_count >= 1, "HASH_WORKERS must be at least 1");
+
+        let dummy_hash = hasher
+            .hash("enumeration-safe dummy password")
+            .unwrap_or_else(|err| panic!("Failed to prepare dummy password hash: {err}"));
+
+        Self {
+            hasher: Arc::new(hasher),
+            workers: Arc::new(Semaphore::new(worker_count)),
+            worker_count,
+            queue_limit: parse_or("HASH_QUEUE_LIMIT", DEFAULT_QUEUE_LIMIT),
+            retry_after_seconds: parse_or("HASH_RETRY_AFTER_SECONDS", DEFAULT_RETRY_AFTER_SECONDS),
+            metrics: Arc::new(HashingMetrics::default()),
+            dummy_hash,
+        }
+    }
+
+    pub async fn hash(&self, password: &str) -> AppResult<String> {
+        let password = password.to_string();
+        self.run(Operation::Hash, move |hasher| hasher.hash(&password)).await
+    }
+
+    pub async fn verify(&self, password: &str, stored_hash: &str, legacy_scheme: Option<&str>) -> AppResult<bool> {
+        let (password, stored_hash) = (password.to_string(), stored_hash.to_string());
+        let legacy_scheme = legacy_scheme.map(str::to_string);
+        self.run(Operation::Verify, move |hasher| {
+            hasher.verify(&password, &stored_hash, legacy_scheme.as_deref())
+        })
+        .await
+    }
+
+    // Spends the same work as checking a real password, for paths where the account doesn't exist
+    pub async fn verify_dummy(&self, password: &str) -> AppResult<()> {
+        self.verify(password, &self.dummy_hash, None).await.map(|_| ())
+    }
+
+    // Only parses the stored hash, cheap enough for the async side
+    pub fn needs_rehash(&self, stored_hash: &str, legacy_scheme: Option<&str>) -> bool {
+        self.hasher.needs_rehash(stored_hash, legacy_scheme)
+    }
+
+    pub fn metrics(&self) -> HashingMetricsSnapshot {
+        let metrics = &self.metrics;
+        let pending = metrics.pending.load(Ordering::Acquire);
+        let running = metrics.running.load(Ordering::Acquire);
+        HashingMetricsSnapshot {
+            workers: self.worker_count,
+            queue_limit: self.queue_limit,
+            queue_depth: pending.saturating_sub(running),
+            in_flight: running,
+            rejected: metrics.rejected.load(Ordering::Relaxed),
+            queue_wait: metrics.queue_wait.snapshot(),
+            hash: metrics.hash.snapshot(),
+            verify: metrics.verify.snapshot(),
+        }
+    }
+
+    async fn run<T, F>(&self, operation: Operation, job: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&PasswordHasher) -> AppResult<T> + Send + 'static,
+    {
+        let admission = self.admit()?;
+
+        let queued_at = Instant::now();
+        let permit = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| AppError::internal("Password hashing pool is closed"))?;
+        self.metrics.queue_wait.record(queued_at.elapsed());
+
+        let hasher = self.hasher.clone();
+        let metrics = self.metrics.clone();
+        tokio::task::spawn_blocking(move || {
+            let _admission = admission;
+            let _permit = permit;
+            metrics.running.fetch_add(1, Ordering::AcqRel);
+            let started_at = Instant::now();
+            let result = job(&hasher);
+            metrics.latency(operation).record(started_at.elapsed());
+            metrics.running.fetch_sub(1, Ordering::AcqRel);
+            result
+        })
+        .await
+        .map_err(|err| AppError::internal(format!("Password hashing task failed: {err}")))?
+    }
+
+    fn admit(&self) -> AppResult<Admission> {
+        let capacity = self.worker_count + self.queue_limit;
+        if self.metrics.pending.fetch_add(1, Ordering::AcqRel) >= capacity {
+            self.metrics.pending.fetch_sub(1, Ordering::AcqRel);
+            self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(capacity, "Password hashing queue is full, shedding request");
+            return Err(AppError::service_unavailable(
+                "Server is busy, try again shortly",
+                self.retry_after_seconds,
+            ));
+        }
+        Ok(Admission(self.metrics.clone()))
+    }
+}
+
+impl HashingMetrics {
+    fn latency(&self, operation: Operation) -> &LatencyStats {
+        match operation {
+            Operation::Hash => &self.hash,
+            Operation::Verify => &self.verify,
+        }
+    }
+}
+
+impl LatencyStats {
+    fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        let count = self.count.load(Ordering::Relaxed);
+        let total_micros = self.total_micros.load(Ordering::Relaxed);
+        LatencySnapshot {
+            count,
+            average_ms: if count == 0 { 0.0 } else { total_micros as f64 / count as f64 / 1000.0 },
+            max_ms: self.max_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use axum::http::{header, StatusCode};
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::test_support::load_env;
+
+    fn pool(worker_count: usize, queue_limit: usize) -> Arc<HashingPool> {
+        load_env();
+        Arc::new(HashingPool {
+            hasher: Arc::new(PasswordHasher::from_env()),
+            workers: Arc::new(Semaphore::new(worker_count)),
+            worker_count,
+            queue_limit,
+            retry_after_seconds: 7,
+            metrics: Arc::new(HashingMetrics::default()),
+            dummy_hash: String::new(),
+        })
+    }
+
+    // Starts a job that holds its worker until the returned sender is used or dropped
+    fn blocking_job(pool: &Arc<HashingPool>) -> (tokio::task::JoinHandle<AppResult<()>>, mpsc::Sender<()>) {
+        let (release, released) = mpsc::channel::<()>();
+        let pool = pool.clone();
+        let handle = tokio::spawn(async move {
+            pool.run(Operation::Verify, move |_| {
+                let _ = released.recv();
+                Ok(())
+            })
+            .await
+        });
+        (handle, release)
+    }
+
+    async fn wait_for(pool: &HashingPool, in_flight: usize, queue_depth: usize) {
+        for _ in 0..200 {
+            let metrics = pool.metrics();
+            if (metrics.in_flight, metrics.queue_depth) == (in_flight, queue_depth) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let metrics = pool.metrics();
+        panic!("Expected {in_flight} running and {queue_depth} queued, got {} and {}", metrics.in_flight, metrics.queue_depth);
+    }
+
+    #[tokio::test]
+    async fn sheds_requests_with_503_and_retry_after_once_workers_and_queue_are_full() {
+        let pool = pool(1, 1);
+        let (running, release_running) = blocking_job(&pool);
+        wait_for(&pool, 1, 0).await;
+        let (queued, release_queued) = blocking_job(&pool);
+        wait_for(&pool, 1, 1).await;
+
+        let err = pool.hash("password").await.unwrap_err();
+        assert!(matches!(err, AppError::ServiceUnavailable { retry_after_seconds: 7, .. }));
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+        assert_eq!(pool.metrics().rejected, 1);
+        assert!(pool.verify_dummy("password").await.is_err());
+        assert_eq!(pool.metrics().rejected, 2);
+
+        drop((release_running, release_queued));
+        running.await.unwrap().unwrap();
+        queued.await.unwrap().unwrap();
+        wait_for(&pool, 0, 0).await;
+        assert_eq!(pool.metrics().rejected, 2);
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_request_keeps_its_slot_until_the_job_finishes() {
+        let pool = pool(1, 1);
+        let (running, release_running) = blocking_job(&pool);
+        wait_for(&pool, 1, 0).await;
+
+        // The caller goes away but the job it started is still on the worker
+        running.abort();
+        assert!(running.await.unwrap_err().is_cancelled());
+        wait_for(&pool, 1, 0).await;
+
+        // Only the single queue slot is left
+        let (queued, release_queued) = blocking_job(&pool);
+        wait_for(&pool, 1, 1).await;
+        assert!(matches!(pool.hash("password").await, Err(AppError::ServiceUnavailable { .. })));
+
+        drop((release_running, release_queued));
+        queued.await.unwrap().unwrap();
+        wait_for(&pool, 0, 0).await;
+    }
+
+    #[tokio::test]
+    async fn a_request_dropped_in_the_queue_frees_its_slot() {
+        let pool = pool(1, 1);
+        let (running, release_running) = blocking_job(&pool);
+        wait_for(&pool, 1, 0).await;
+        let (queued, _release_queued) = blocking_job(&pool);
+        wait_for(&pool, 1, 1).await;
+
+        queued.abort();
+        assert!(queued.await.unwrap_err().is_cancelled());
+        wait_for(&pool, 1, 0).await;
+
+        drop(release_running);
+        running.await.unwrap().unwrap();
+        wait_for(&pool, 0, 0).await;
+    }
+}
@@ -0,0 +1,84 @@
+use sqlx::{PgConnection, PgExecutor};
+
+use crate::crud::error_traits::AppResult;
+use crate::passwords::model::PasswordHistoryEntry;
+
+
+// Records a newly set password and drops entries beyond the `keep` most recent
+pub async fn record_password_history_repository(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    password_hash: &str,
+    password_scheme: Option<&str>,
+    keep: i64,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO password_history (credential_id, password_hash, password_scheme)
+        VALUES ($1, $2, $3)
+        "#,
+        credential_id,
+        password_hash,
+        password_scheme
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM password_history
+        WHERE credential_id = $1
+          AND id NOT IN (
+              SELECT id FROM password_history
+              WHERE credential_id = $1
+              ORDER BY created_at DESC, id DESC
+              LIMIT $2
+          )
+        "#,
+        credential_id,
+        keep
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_password_history_entry_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    password_hash: &str,
+) -> AppResult<()> {
+    sqlx::query!(
+        "DELETE FROM password_history WHERE credential_id = $1 AND password_hash = $2",
+        credential_id,
+        password_hash
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn recent_password_history_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    limit: i64,
+) -> AppResult<Vec<PasswordHistoryEntry>> {
+    let entries = sqlx::query_as!(
+        PasswordHistoryEntry,
+        r#"
+        SELECT password_hash, password_scheme
+        FROM password_history
+        WHERE credential_id = $1
+        ORDER BY created_at DESC, id DESC
+        LIMIT $2
+        "#,
+        credential_id,
+        limit
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(entries)
+}
@@ -0,0 +1,11 @@
+use axum::{
+    routing::get,
+    Router
+};
+use crate::passwords::handler::hashing_metrics_handler;
+use crate::grouped_routes::main_route::AppState;
+
+pub fn metrics_routes() -> Router<AppState> {
+    Router::new()
+      .route("/hashing", get(hashing_metrics_handler))
+}
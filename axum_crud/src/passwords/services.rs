@@ -0,0 +1,152 @@
+use sqlx::{PgConnection, PgPool};
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::passwords::pool::HashingPool;
+use crate::passwords::repository::{
+    delete_password_history_entry_repository, recent_password_history_repository, record_password_history_repository,
+};
+
+
+// The history always includes the current password, so a depth of 1 only forbids keeping it and 0
+// turns the check off
+// Runs before the caller's transaction, the Argon2 verifies are too slow to hold row locks through
+pub async fn ensure_password_not_reused_service(
+    pool: &PgPool,
+    credential_id: i32,
+    password: &str,
+    depth: i64,
+    passwords: &HashingPool,
+) -> AppResult<()> {
+    if depth == 0 {
+        return Ok(());
+    }
+
+    for entry in recent_password_history_repository(pool, credential_id, depth).await? {
+        // An entry that can no longer be verified can't be matched either, it shouldn't block the change;
+        // a busy hashing pool still fails the request rather than skipping the check
+        match passwords.verify(password, &entry.password_hash, entry.password_scheme.as_deref()).await {
+            Ok(true) => return Err(AppError::PasswordReused { depth }),
+            Ok(false) | Err(AppError::PasswordHashing(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+// Keeps at least the current password even with the check off, so raising the depth later has something to compare
+pub async fn record_password_history_service(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    password_hash: &str,
+    depth: i64,
+) -> AppResult<()> {
+    record_password_history_repository(conn, credential_id, password_hash, None, depth.max(1)).await
+}
+
+// A login rehash stores the same password under a new hash. The new hash takes the old one's place,
+// so the password still counts once against the depth.
+pub async fn record_rehashed_password_service(
+    conn: &mut PgConnection,
+    credential_id: i32,
+    old_hash: &str,
+    new_hash: &str,
+    depth: i64,
+) -> AppResult<()> {
+    delete_password_history_entry_repository(&mut *conn, credential_id, old_hash).await?;
+    record_password_history_service(conn, credential_id, new_hash, depth).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    use super::*;
+    use crate::grouped_routes::main_route::AppState;
+    use crate::test_support::{create_account, send_json, test_state, RecordingMailer};
+
+    const EMAIL: &str = "history@example.com";
+    // Oldest first; the last one is the current password
+    const PASSWORDS: [&str; 4] = ["First-Lantern-Ridge-1", "Second-Copper-Field-2", "Third-Maple-Harbor-3", "Fourth-Quiet-Signal-4"];
+
+    fn state(pool: PgPool) -> AppState {
+        test_state(pool, Arc::new(RecordingMailer::default()), |config| config.allow_unverified_login = true)
+    }
+
+    // Sets each of PASSWORDS in turn, keeping every entry
+    async fn with_history(state: &AppState) -> i32 {
+        let id = create_account(&state.db, EMAIL).await;
+        for password in PASSWORDS {
+            let hash = state.passwords.hash(password).await.unwrap();
+            let mut conn = state.db.acquire().await.unwrap();
+            record_password_history_service(&mut conn, id, &hash, PASSWORDS.len() as i64).await.unwrap();
+        }
+        id
+    }
+
+    async fn check(state: &AppState, id: i32, password: &str, depth: i64) -> AppResult<()> {
+        ensure_password_not_reused_service(&state.db, id, password, depth, &state.passwords).await
+    }
+
+    #[sqlx::test]
+    async fn rejects_the_last_depth_passwords_and_allows_older_ones(pool: PgPool) {
+        let state = state(pool);
+        let id = with_history(&state).await;
+        let depth = 3;
+
+        // The current password and the two before it are within the last 3
+        for password in &PASSWORDS[1..] {
+            let err = check(&state, id, password, depth).await.unwrap_err();
+            assert!(matches!(err, AppError::PasswordReused { depth: 3 }), "{password} should be refused, got {err:?}");
+        }
+        // The fourth most recent is one past the depth
+        check(&state, id, PASSWORDS[0], depth).await.unwrap();
+        assert!(matches!(check(&state, id, PASSWORDS[0], depth + 1).await, Err(AppError::PasswordReused { depth: 4 })));
+        check(&state, id, "Never-Used-Before-5", depth).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn a_depth_of_zero_turns_the_check_off(pool: PgPool) {
+        let state = state(pool);
+        let id = with_history(&state).await;
+
+        for password in PASSWORDS {
+            check(&state, id, password, 0).await.unwrap();
+        }
+    }
+
+    #[sqlx::test]
+    async fn a_login_rehash_replaces_the_old_hash_in_the_history(pool: PgPool) {
+        let state = state(pool);
+        let id = create_account(&state.db, EMAIL).await;
+        let password = PASSWORDS[0];
+        let bcrypt_hash = bcrypt::hash(password, 4).unwrap();
+        sqlx::query!("UPDATE credentials SET password = $1 WHERE id = $2", bcrypt_hash, id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let mut conn = state.db.acquire().await.unwrap();
+        record_password_history_service(&mut conn, id, &bcrypt_hash, 5).await.unwrap();
+        drop(conn);
+
+        let login = send_json(&state, Method::POST, "/crud/login", json!({ "email": EMAIL, "password": password })).await;
+        assert_eq!(login.status, StatusCode::OK);
+
+        let current = sqlx::query_scalar!("SELECT password FROM credentials WHERE id = $1", id)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert!(current.starts_with("$argon2id$"));
+        let history: Vec<_> = recent_password_history_repository(&state.db, id, 5)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.password_hash)
+            .collect();
+        assert_eq!(history, [current]);
+        assert!(matches!(check(&state, id, password, 1).await, Err(AppError::PasswordReused { depth: 1 })));
+    }
+}
@@ -0,0 +1,61 @@
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::repository::get_stored_credentials_by_id_repository;
+use crate::auth::extractor::Authenticated;
+use crate::rbac::extractor::Principal;
+use crate::rbac::repository::{credential_permissions_repository, credential_roles_repository};
+
+// Attributes policies can refer to as `subject.*`
+pub async fn subject_attributes(principal: &Principal, pool: &PgPool) -> AppResult<Value> {
+    let credential_id = principal.credential_id();
+    let credentials = get_stored_credentials_by_id_repository(credential_id, pool)
+        .await?
+        .ok_or_else(|| AppError::authentication("Account no longer exists"))?;
+
+    let auth_method = match principal {
+        Principal::User(Authenticated::Bearer(_)) => "bearer",
+        Principal::User(Authenticated::Session(_)) => "session",
+        Principal::ApiKey(_) => "api_key",
+    };
+
+    Ok(json!({
+        "id": credentials.id,
+        "email": credentials.email,
+        "email_verified": credentials.email_verified_at.is_some(),
+        "roles": credential_roles_repository(pool, credential_id).await?,
+        "permissions": credential_permissions_repository(pool, credential_id).await?,
+        "auth_method": auth_method,
+    }))
+}
+
+// `resource.*` for an account; the account owns itself
+pub fn credentials_resource(id: i32, email: &str, email_verified: bool) -> Value {
+    json!({
+        "type": "credentials",
+        "id": id,
+        "owner_id": id,
+        "email": email,
+        "email_verified": email_verified,
+    })
+}
+
+// Stand-in when the target doesn't exist, so rules that don't depend on it can still decide
+pub fn missing_resource(resource_type: &str) -> Value {
+    json!({ "type": resource_type })
+}
+
+// Resources that can be named in /policy/explain
+pub async fn load_resource(resource_type: &str, id: Option<i32>, pool: &PgPool) -> AppResult<Value> {
+    match (resource_type, id) {
+        ("credentials", Some(id)) => Ok(get_stored_credentials_by_id_repository(id, pool)
+            .await?
+            .map(|credentials| {
+                credentials_resource(credentials.id, &credentials.email, credentials.email_verified_at.is_some())
+            })
+            .unwrap_or_else(|| missing_resource(resource_type))),
+        ("credentials", None) => Ok(missing_resource(resource_type)),
+        _ => Err(AppError::validation(format!("Unknown resource type: {resource_type}"))),
+    }
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize};
+
+// Ask how the policies decide `action` on a resource for the caller; without `resource_id` the resource is left blank
+#[derive(Deserialize,Debug)]
+pub struct ExplainRequest {
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<i32>,
+}
@@ -0,0 +1,388 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Attribute-based policies. Rules are plain data (see policies.json) and evaluation is a pure function of
+// the request, with no HTTP or database involved; callers gather the attributes first.
+//
+// A request is allowed when at least one `allow` rule matches and no `deny` rule does. A rule matches when
+// one of its actions covers the requested action and all of its conditions hold.
+
+const DEFAULT_POLICY_FILE: &str = "policies.json";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    // Left side is an array containing the right side
+    Contains,
+    // Left side is one of the elements of the right side
+    In,
+    // Left side is present and not null; takes no right side
+    Exists,
+}
+
+// `attribute` is a dotted path into the request, e.g. `subject.roles` or `resource.owner_id`.
+// The right side is either a literal `value` or another attribute via `attribute_ref`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    pub attribute: String,
+    pub operator: Operator,
+    pub value: Option<Value>,
+    pub attribute_ref: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    pub effect: Effect,
+    // Same syntax as API key scopes: `credentials:read`, `credentials:*` or `*`
+    pub actions: Vec<String>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    rules: Vec<Rule>,
+}
+
+pub struct AccessRequest<'a> {
+    pub subject: &'a Value,
+    pub action: &'a str,
+    pub resource: &'a Value,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOutcome {
+    NotApplicable,
+    Matched,
+    ConditionsFailed,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConditionTrace {
+    pub condition: String,
+    pub holds: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RuleTrace {
+    pub rule: String,
+    pub description: String,
+    pub effect: Effect,
+    pub outcome: RuleOutcome,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<ConditionTrace>,
+}
+
+// The decision together with how every rule evaluated, so a denial can be explained
+#[derive(Serialize, Debug)]
+pub struct Decision {
+    pub allowed: bool,
+    pub action: String,
+    pub decided_by: Option<String>,
+    pub reason: String,
+    pub rules: Vec<RuleTrace>,
+}
+
+pub struct PolicyEngine {
+    rules: Vec<Rule>,
+}
+
+impl PolicyEngine {
+    // Reads POLICY_FILE (default policies.json); an unreadable or invalid file stops startup
+    pub fn from_env() -> Self {
+        let path = std::env::var("POLICY_FILE").unwrap_or_else(|_| DEFAULT_POLICY_FILE.to_string());
+        let contents = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("Failed to read policy file {path}: {err}"));
+        Self::from_json(&contents).unwrap_or_else(|err| panic!("Invalid policy file {path}: {err}"))
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, String> {
+        let file: PolicyFile = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        Self::new(file.rules)
+    }
+
+    pub fn new(rules: Vec<Rule>) -> Result<Self, String> {
+        let mut ids = HashSet::new();
+        for rule in &rules {
+            if !ids.insert(rule.id.as_str()) {
+                return Err(format!("duplicate rule id {}", rule.id));
+            }
+            if rule.actions.is_empty() {
+                return Err(format!("rule {} has no actions", rule.id));
+            }
+            for condition in &rule.conditions {
+                validate_condition(condition).map_err(|err| format!("rule {}: {err}", rule.id))?;
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn evaluate(&self, request: &AccessRequest) -> Decision {
+        let rules: Vec<RuleTrace> = self.rules.iter().map(|rule| trace_rule(rule, request)).collect();
+
+        let first_match = |effect| {
+            rules
+                .iter()
+                .find(|trace| trace.effect == effect && trace.outcome == RuleOutcome::Matched)
+                .map(|trace| trace.rule.clone())
+        };
+
+        let (allowed, decided_by, reason) = if let Some(rule) = first_match(Effect::Deny) {
+            (false, Some(rule.clone()), format!("Denied by rule {rule}"))
+        } else if let Some(rule) = first_match(Effect::Allow) {
+            (true, Some(rule.clone()), format!("Allowed by rule {rule}"))
+        } else {
+            (false, None, "No rule allows this action".to_string())
+        };
+
+        Decision {
+            allowed,
+            action: request.action.to_string(),
+            decided_by,
+            reason,
+            rules,
+        }
+    }
+}
+
+pub fn action_matches(pattern: &str, action: &str) -> bool {
+    pattern == "*"
+        || pattern == action
+        || pattern
+            .strip_suffix(":*")
+            .is_some_and(|resource| action.strip_prefix(resource).is_some_and(|rest| rest.starts_with(':')))
+}
+
+fn validate_condition(condition: &Condition) -> Result<(), String> {
+    validate_path(&condition.attribute)?;
+    if let Some(path) = &condition.attribute_ref {
+        validate_path(path)?;
+    }
+
+    let operands = usize::from(condition.value.is_some()) + usize::from(condition.attribute_ref.is_some());
+    match (condition.operator, operands) {
+        (Operator::Exists, 0) => Ok(()),
+        (Operator::Exists, _) => Err(format!("{} exists takes no value or attribute_ref", condition.attribute)),
+        (_, 1) => Ok(()),
+        _ => Err(format!("{} needs exactly one of value or attribute_ref", condition.attribute)),
+    }
+}
+
+fn validate_path(path: &str) -> Result<(), String> {
+    match path.split_once('.') {
+        Some(("subject" | "resource", rest)) if !rest.is_empty() => Ok(()),
+        _ => Err(format!("attribute {path} must start with subject. or resource.")),
+    }
+}
+
+fn trace_rule(rule: &Rule, request: &AccessRequest) -> RuleTrace {
+    let applicable = rule.actions.iter().any(|pattern| action_matches(pattern, request.action));
+
+    let conditions: Vec<ConditionTrace> = if applicable {
+        rule.conditions
+            .iter()
+            .map(|condition| ConditionTrace {
+                condition: describe(condition),
+                holds: holds(condition, request),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let outcome = if !applicable {
+        RuleOutcome::NotApplicable
+    } else if conditions.iter().all(|trace| trace.holds) {
+        RuleOutcome::Matched
+    } else {
+        RuleOutcome::ConditionsFailed
+    };
+
+    RuleTrace {
+        rule: rule.id.clone(),
+        description: rule.description.clone(),
+        effect: rule.effect,
+        outcome,
+        conditions,
+    }
+}
+
+fn lookup<'a>(request: &'a AccessRequest, path: &str) -> Option<&'a Value> {
+    let mut segments = path.split('.');
+    let mut current = match segments.next()? {
+        "subject" => request.subject,
+        "resource" => request.resource,
+        _ => return None,
+    };
+    for segment in segments {
+        current = current.get(segment)?;
+    }
+    Some(current).filter(|value| !value.is_null())
+}
+
+// Missing attributes never satisfy a comparison, so a rule can't match by accident on absent data
+fn holds(condition: &Condition, request: &AccessRequest) -> bool {
+    let left = lookup(request, &condition.attribute);
+    let right = match (&condition.value, &condition.attribute_ref) {
+        (Some(value), _) => Some(value),
+        (None, Some(path)) => lookup(request, path),
+        (None, None) => None,
+    };
+
+    match (condition.operator, left, right) {
+        (Operator::Exists, left, _) => left.is_some(),
+        (Operator::Equals, Some(left), Some(right)) => left == right,
+        (Operator::NotEquals, Some(left), Some(right)) => left != right,
+        (Operator::Contains, Some(Value::Array(items)), Some(right)) => items.contains(right),
+        (Operator::In, Some(left), Some(Value::Array(items))) => items.contains(left),
+        _ => false,
+    }
+}
+
+fn describe(condition: &Condition) -> String {
+    let operator = match condition.operator {
+        Operator::Equals => "equals",
+        Operator::NotEquals => "not_equals",
+        Operator::Contains => "contains",
+        Operator::In => "in",
+        Operator::Exists => "exists",
+    };
+    match (&condition.value, &condition.attribute_ref) {
+        (Some(value), _) => format!("{} {operator} {value}", condition.attribute),
+        (None, Some(path)) => format!("{} {operator} {path}", condition.attribute),
+        (None, None) => format!("{} {operator}", condition.attribute),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const OWNER_RULES: &str = r#"{"rules": [
+        {"id": "owners-read-own", "effect": "allow", "actions": ["credentials:read"],
+         "conditions": [{"attribute": "resource.owner_id", "operator": "equals", "attribute_ref": "subject.id"}]},
+        {"id": "readers-read-any", "effect": "allow", "actions": ["credentials:*"],
+         "conditions": [{"attribute": "subject.roles", "operator": "contains", "value": "reader"}]},
+        {"id": "suspended-denied", "effect": "deny", "actions": ["*"],
+         "conditions": [{"attribute": "subject.suspended", "operator": "equals", "value": true}]}
+    ]}"#;
+
+    fn engine() -> PolicyEngine {
+        PolicyEngine::from_json(OWNER_RULES).unwrap()
+    }
+
+    fn decide(subject: Value, action: &str, resource: Value) -> Decision {
+        engine().evaluate(&AccessRequest {
+            subject: &subject,
+            action,
+            resource: &resource,
+        })
+    }
+
+    fn trace_of<'a>(decision: &'a Decision, rule: &str) -> &'a RuleTrace {
+        decision.rules.iter().find(|trace| trace.rule == rule).unwrap()
+    }
+
+    #[test]
+    fn denies_when_no_rule_allows() {
+        let decision = decide(json!({ "id": 1, "roles": [] }), "credentials:read", json!({ "owner_id": 2 }));
+
+        assert!(!decision.allowed);
+        assert_eq!(decision.decided_by, None);
+        assert_eq!(decision.reason, "No rule allows this action");
+    }
+
+    #[test]
+    fn denies_actions_no_rule_covers() {
+        let decision = decide(json!({ "id": 1, "roles": ["reader"] }), "roles:assign", json!({}));
+
+        assert!(!decision.allowed);
+        assert_eq!(trace_of(&decision, "readers-read-any").outcome, RuleOutcome::NotApplicable);
+    }
+
+    #[test]
+    fn allows_the_owner_of_the_resource() {
+        let decision = decide(json!({ "id": 7, "roles": [] }), "credentials:read", json!({ "owner_id": 7 }));
+
+        assert!(decision.allowed);
+        assert_eq!(decision.decided_by.as_deref(), Some("owners-read-own"));
+    }
+
+    #[test]
+    fn does_not_allow_anyone_but_the_owner() {
+        let decision = decide(json!({ "id": 7, "roles": [] }), "credentials:read", json!({ "owner_id": 8 }));
+
+        assert!(!decision.allowed);
+        assert_eq!(trace_of(&decision, "owners-read-own").outcome, RuleOutcome::ConditionsFailed);
+    }
+
+    #[test]
+    fn explicit_deny_beats_an_allow() {
+        let subject = json!({ "id": 7, "roles": ["reader"], "suspended": true });
+        let decision = decide(subject, "credentials:read", json!({ "owner_id": 7 }));
+
+        assert!(!decision.allowed);
+        assert_eq!(decision.decided_by.as_deref(), Some("suspended-denied"));
+        assert_eq!(trace_of(&decision, "owners-read-own").outcome, RuleOutcome::Matched);
+    }
+
+    #[test]
+    fn missing_attributes_never_satisfy_a_condition() {
+        // Neither side of owner_id == id is present, which must not count as equal
+        let decision = decide(json!({ "roles": [] }), "credentials:read", json!({}));
+        assert!(!decision.allowed);
+        assert_eq!(trace_of(&decision, "owners-read-own").outcome, RuleOutcome::ConditionsFailed);
+
+        // The same goes for a deny: an absent `suspended` doesn't trigger it
+        let decision = decide(json!({ "id": 7, "roles": [] }), "credentials:read", json!({ "owner_id": 7 }));
+        assert!(decision.allowed);
+        assert_eq!(trace_of(&decision, "suspended-denied").outcome, RuleOutcome::ConditionsFailed);
+    }
+
+    #[test]
+    fn rejects_malformed_policy_files() {
+        assert!(PolicyEngine::from_json("{ not json").is_err());
+        assert!(PolicyEngine::from_json(r#"{"rules": [{"id": "a", "effect": "permit", "actions": ["*"]}]}"#).is_err());
+        assert!(PolicyEngine::from_json(r#"{"rules": [], "extra": true}"#).is_err());
+
+        let err = PolicyEngine::from_json(
+            r#"{"rules": [{"id": "a", "effect": "allow", "actions": ["*"]}, {"id": "a", "effect": "deny", "actions": ["*"]}]}"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err, "duplicate rule id a");
+
+        let err = PolicyEngine::from_json(
+            r#"{"rules": [{"id": "a", "effect": "allow", "actions": ["*"],
+                "conditions": [{"attribute": "owner_id", "operator": "exists"}]}]}"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err, "rule a: attribute owner_id must start with subject. or resource.");
+    }
+
+    #[test]
+    fn loads_the_shipped_policy_file() {
+        let contents = std::fs::read_to_string(DEFAULT_POLICY_FILE).unwrap();
+        assert!(PolicyEngine::from_json(&contents).is_ok());
+    }
+}
@@ -0,0 +1,21 @@
+use axum::{
+    extract::{State},
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::policy::dto::ExplainRequest;
+use crate::policy::services::explain_service;
+use crate::rbac::extractor::Principal;
+
+#[axum::debug_handler]
+pub async fn explain_handler(
+    State(state): State<AppState>,
+    principal: Principal,
+    Json(body): Json<ExplainRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = explain_service(&principal, body, &state.db, &state.policy).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
@@ -0,0 +1,6 @@
+pub mod engine;
+pub mod attributes;
+pub mod dto;
+pub mod services;
+pub mod handler;
+pub mod routes;
@@ -0,0 +1,11 @@
+use axum::{
+    routing::post,
+    Router
+};
+use crate::policy::handler::explain_handler;
+use crate::grouped_routes::main_route::AppState;
+
+pub fn policy_routes() -> Router<AppState> {
+    Router::new()
+      .route("/explain", post(explain_handler))
+}
@@ -0,0 +1,63 @@
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::policy::attributes::{load_resource, subject_attributes};
+use crate::policy::dto::ExplainRequest;
+use crate::policy::engine::{AccessRequest, Decision, PolicyEngine};
+use crate::rbac::extractor::Principal;
+
+
+// API keys are held to their scopes before any rule is consulted, as with RequirePermission
+pub async fn decide_service(
+    principal: &Principal,
+    action: &str,
+    resource: &Value,
+    pool: &PgPool,
+    engine: &PolicyEngine,
+) -> AppResult<Decision> {
+    if let Principal::ApiKey(key) = principal
+        && !key.has_scope(action)
+    {
+        return Ok(Decision {
+            allowed: false,
+            action: action.to_string(),
+            decided_by: None,
+            reason: format!("API key is missing the {action} scope"),
+            rules: Vec::new(),
+        });
+    }
+
+    let subject = subject_attributes(principal, pool).await?;
+    Ok(engine.evaluate(&AccessRequest {
+        subject: &subject,
+        action,
+        resource,
+    }))
+}
+
+// 403 AUTHORIZATION_FAILED with the decision's reason unless the policies allow the action
+pub async fn authorize_service(
+    principal: &Principal,
+    action: &str,
+    resource: &Value,
+    pool: &PgPool,
+    engine: &PolicyEngine,
+) -> AppResult<()> {
+    let decision = decide_service(principal, action, resource, pool, engine).await?;
+    if !decision.allowed {
+        return Err(AppError::authorization(decision.reason));
+    }
+    Ok(())
+}
+
+// Only ever explains decisions for the caller, and never echoes the resource's attributes back
+pub async fn explain_service(
+    principal: &Principal,
+    input: ExplainRequest,
+    pool: &PgPool,
+    engine: &PolicyEngine,
+) -> AppResult<Decision> {
+    let resource = load_resource(&input.resource_type, input.resource_id, pool).await?;
+    decide_service(principal, &input.action, &resource, pool, engine).await
+}
@@ -0,0 +1,6 @@
+use serde::{Deserialize};
+
+#[derive(Deserialize,Debug)]
+pub struct AssignRoleRequest {
+    pub role: String,
+}
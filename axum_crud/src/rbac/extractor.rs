@@ -0,0 +1,72 @@
+use std::marker::PhantomData;
+
+use axum::{
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+
+use crate::api_keys::extractor::{has_api_key, ApiKey};
+use crate::auth::extractor::Authenticated;
+use crate::crud::error_traits::AppError;
+use crate::grouped_routes::main_route::AppState;
+use crate::rbac::permissions::Permission;
+use crate::rbac::repository::has_permission_repository;
+
+// Whoever is making the request: a logged-in user (bearer token or session) or one of their API keys
+#[derive(Debug, Clone)]
+pub enum Principal {
+    User(Authenticated),
+    ApiKey(ApiKey),
+}
+
+impl Principal {
+    pub fn credential_id(&self) -> i32 {
+        match self {
+            Principal::User(auth) => auth.credential_id(),
+            Principal::ApiKey(key) => key.credential_id,
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for Principal {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if has_api_key(parts) {
+            ApiKey::from_request_parts(parts, state).await.map(Principal::ApiKey)
+        } else {
+            Authenticated::from_request_parts(parts, state).await.map(Principal::User)
+        }
+    }
+}
+
+// Add `guard: RequirePermission<SomePermission>` to a handler's arguments to require that permission through
+// one of the caller's roles: 401 when not logged in, 403 AUTHORIZATION_FAILED when the permission is missing.
+// API keys additionally need a matching scope, so a key can never do more than its owner.
+pub struct RequirePermission<P: Permission> {
+    pub principal: Principal,
+    _permission: PhantomData<P>,
+}
+
+impl<P: Permission> FromRequestParts<AppState> for RequirePermission<P> {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let principal = Principal::from_request_parts(parts, state).await?;
+
+        if let Principal::ApiKey(key) = &principal
+            && !key.has_scope(P::NAME)
+        {
+            return Err(AppError::authorization(format!("API key is missing the {} scope", P::NAME)));
+        }
+
+        if !has_permission_repository(&state.db, principal.credential_id(), P::NAME).await? {
+            return Err(AppError::authorization(format!("Missing permission {}", P::NAME)));
+        }
+
+        Ok(RequirePermission {
+            principal,
+            _permission: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::rbac::dto::AssignRoleRequest;
+use crate::rbac::extractor::RequirePermission;
+use crate::rbac::permissions::RolesManage;
+use crate::rbac::services::{list_roles_service,credential_roles_service,assign_role_service,revoke_role_service};
+
+#[axum::debug_handler]
+pub async fn list_roles_handler(
+    State(state): State<AppState>,
+    _guard: RequirePermission<RolesManage>,
+) -> AppResult<impl IntoResponse> {
+    let response = list_roles_service(&state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn credential_roles_handler(
+    State(state): State<AppState>,
+    _guard: RequirePermission<RolesManage>,
+    Path(credential_id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    let response = credential_roles_service(credential_id, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn assign_role_handler(
+    State(state): State<AppState>,
+    guard: RequirePermission<RolesManage>,
+    Path(credential_id): Path<i32>,
+    Json(body): Json<AssignRoleRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = assign_role_service(guard.principal.credential_id(), credential_id, body, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn revoke_role_handler(
+    State(state): State<AppState>,
+    guard: RequirePermission<RolesManage>,
+    Path((credential_id, role)): Path<(i32, String)>,
+) -> AppResult<impl IntoResponse> {
+    revoke_role_service(guard.principal.credential_id(), credential_id, &role, &state.db).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,8 @@
+pub mod permissions;
+pub mod extractor;
+pub mod dto;
+pub mod model;
+pub mod repository;
+pub mod services;
+pub mod handler;
+pub mod routes;
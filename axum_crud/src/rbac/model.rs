@@ -0,0 +1,15 @@
+use serde::{Serialize};
+
+
+#[derive(Serialize)]
+pub struct RoleResponse {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct CredentialRolesResponse {
+    pub credential_id: i32,
+    pub roles: Vec<String>,
+}
@@ -0,0 +1,21 @@
+// Permissions are zero-sized marker types so a route's requirement is part of its handler signature:
+// `_: RequirePermission<CredentialsRead>`. The names must exist in the `permissions` table.
+pub trait Permission: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+macro_rules! permission {
+    ($marker:ident, $name:literal) => {
+        pub struct $marker;
+
+        impl Permission for $marker {
+            const NAME: &'static str = $name;
+        }
+    };
+}
+
+permission!(CredentialsRead, "credentials:read");
+permission!(RolesManage, "roles:manage");
+permission!(LockoutsManage, "lockouts:manage");
+permission!(CredentialsImport, "credentials:import");
+permission!(MetricsRead, "metrics:read");
@@ -0,0 +1,164 @@
+use sqlx::{PgConnection, PgExecutor, PgPool};
+
+use crate::crud::error_traits::AppResult;
+use crate::rbac::model::RoleResponse;
+
+
+pub async fn has_permission_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    permission: &str,
+) -> AppResult<bool> {
+    let granted = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM credential_roles
+            JOIN role_permissions ON role_permissions.role_id = credential_roles.role_id
+            JOIN permissions ON permissions.id = role_permissions.permission_id
+            WHERE credential_roles.credential_id = $1 AND permissions.name = $2
+        ) AS "granted!"
+        "#,
+        credential_id,
+        permission
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(granted)
+}
+
+pub async fn list_roles_repository(
+    pool: &PgPool,
+) -> AppResult<Vec<RoleResponse>> {
+    let records = sqlx::query!(
+        r#"
+        SELECT roles.name, roles.description,
+               COALESCE(ARRAY_AGG(permissions.name ORDER BY permissions.name)
+                        FILTER (WHERE permissions.name IS NOT NULL), '{}') AS "permissions!"
+        FROM roles
+        LEFT JOIN role_permissions ON role_permissions.role_id = roles.id
+        LEFT JOIN permissions ON permissions.id = role_permissions.permission_id
+        GROUP BY roles.id
+        ORDER BY roles.name
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| RoleResponse {
+            name: r.name,
+            description: r.description,
+            permissions: r.permissions,
+        })
+        .collect())
+}
+
+pub async fn credential_roles_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<Vec<String>> {
+    let roles = sqlx::query_scalar!(
+        r#"
+        SELECT roles.name
+        FROM credential_roles
+        JOIN roles ON roles.id = credential_roles.role_id
+        WHERE credential_roles.credential_id = $1
+        ORDER BY roles.name
+        "#,
+        credential_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(roles)
+}
+
+pub async fn credential_permissions_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<Vec<String>> {
+    let permissions = sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT permissions.name
+        FROM credential_roles
+        JOIN role_permissions ON role_permissions.role_id = credential_roles.role_id
+        JOIN permissions ON permissions.id = role_permissions.permission_id
+        WHERE credential_roles.credential_id = $1
+        ORDER BY permissions.name
+        "#,
+        credential_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(permissions)
+}
+
+// False when the role doesn't exist; granting a role twice is a no-op
+pub async fn assign_role_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    role: &str,
+) -> AppResult<bool> {
+    let role_id = sqlx::query_scalar!(
+        r#"
+        WITH role AS (SELECT id FROM roles WHERE name = $2),
+        granted AS (
+            INSERT INTO credential_roles (credential_id, role_id)
+            SELECT $1, id FROM role
+            ON CONFLICT DO NOTHING
+        )
+        SELECT id FROM role
+        "#,
+        credential_id,
+        role
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(role_id.is_some())
+}
+
+pub async fn revoke_role_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+    role: &str,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM credential_roles
+        USING roles
+        WHERE credential_roles.role_id = roles.id AND credential_roles.credential_id = $1 AND roles.name = $2
+        "#,
+        credential_id,
+        role
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Locks the role's assignments so concurrent revocations can't both pass the last-holder check
+pub async fn count_role_holders_for_update_repository(
+    conn: &mut PgConnection,
+    role: &str,
+) -> AppResult<i64> {
+    let holders = sqlx::query_scalar!(
+        r#"
+        SELECT credential_roles.credential_id
+        FROM credential_roles
+        JOIN roles ON roles.id = credential_roles.role_id
+        WHERE roles.name = $1
+        FOR UPDATE OF credential_roles
+        "#,
+        role
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(holders.len() as i64)
+}
@@ -0,0 +1,13 @@
+use axum::{
+    routing::{get, delete},
+    Router
+};
+use crate::rbac::handler::{list_roles_handler,credential_roles_handler,assign_role_handler,revoke_role_handler};
+use crate::grouped_routes::main_route::AppState;
+
+pub fn rbac_routes() -> Router<AppState> {
+    Router::new()
+      .route("/roles", get(list_roles_handler))
+      .route("/credentials/{id}/roles", get(credential_roles_handler).post(assign_role_handler))
+      .route("/credentials/{id}/roles/{role}", delete(revoke_role_handler))
+}
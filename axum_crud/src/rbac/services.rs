@@ -0,0 +1,104 @@
+use sqlx::PgPool;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::repository::{get_stored_credentials_by_email_repository, get_stored_credentials_by_id_repository};
+use crate::rbac::dto::AssignRoleRequest;
+use crate::rbac::model::{CredentialRolesResponse, RoleResponse};
+use crate::rbac::repository::{
+    assign_role_repository, count_role_holders_for_update_repository, credential_roles_repository,
+    list_roles_repository, revoke_role_repository,
+};
+
+// Seeded by the RBAC migration
+pub const ADMIN_ROLE: &str = "admin";
+
+
+pub async fn list_roles_service(pool: &PgPool) -> AppResult<Vec<RoleResponse>> {
+    list_roles_repository(pool).await
+}
+
+pub async fn credential_roles_service(
+    credential_id: i32,
+    pool: &PgPool,
+) -> AppResult<CredentialRolesResponse> {
+    ensure_credential_exists(credential_id, pool).await?;
+
+    Ok(CredentialRolesResponse {
+        credential_id,
+        roles: credential_roles_repository(pool, credential_id).await?,
+    })
+}
+
+pub async fn assign_role_service(
+    actor_id: i32,
+    credential_id: i32,
+    input: AssignRoleRequest,
+    pool: &PgPool,
+) -> AppResult<CredentialRolesResponse> {
+    ensure_credential_exists(credential_id, pool).await?;
+
+    if !assign_role_repository(pool, credential_id, &input.role).await? {
+        return Err(AppError::not_found("Role"));
+    }
+    tracing::info!(actor_id, credential_id, role = %input.role, "Role granted");
+
+    credential_roles_service(credential_id, pool).await
+}
+
+// The last admin can't be removed, otherwise nobody could ever grant roles again
+pub async fn revoke_role_service(
+    actor_id: i32,
+    credential_id: i32,
+    role: &str,
+    pool: &PgPool,
+) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+
+    if role == ADMIN_ROLE && count_role_holders_for_update_repository(&mut tx, role).await? <= 1 {
+        let roles = credential_roles_repository(&mut *tx, credential_id).await?;
+        if roles.iter().any(|held| held == ADMIN_ROLE) {
+            return Err(AppError::conflict("Cannot remove the last admin"));
+        }
+    }
+
+    if !revoke_role_repository(&mut *tx, credential_id, role).await? {
+        return Err(AppError::not_found("Role assignment"));
+    }
+    tx.commit().await?;
+    tracing::info!(actor_id, credential_id, role, "Role revoked");
+
+    Ok(())
+}
+
+// Grants the admin role to BOOTSTRAP_ADMIN_EMAIL at startup while no admin exists, so a fresh install has
+// someone who can grant roles; after that, grants only go through /rbac
+pub async fn bootstrap_admin_service(pool: &PgPool) -> AppResult<()> {
+    let Ok(email) = std::env::var("BOOTSTRAP_ADMIN_EMAIL") else {
+        return Ok(());
+    };
+    let normalized_email = email.to_lowercase().trim().to_string();
+
+    let mut tx = pool.begin().await?;
+    if count_role_holders_for_update_repository(&mut tx, ADMIN_ROLE).await? > 0 {
+        return Ok(());
+    }
+
+    // Anyone can register an address, so only its verified owner is trusted with the role
+    match get_stored_credentials_by_email_repository(&normalized_email, pool).await? {
+        Some(credentials) if credentials.email_verified_at.is_some() => {
+            assign_role_repository(&mut *tx, credentials.id, ADMIN_ROLE).await?;
+            tx.commit().await?;
+            tracing::info!(credential_id = credentials.id, "Granted admin to BOOTSTRAP_ADMIN_EMAIL");
+        }
+        Some(_) => tracing::warn!("BOOTSTRAP_ADMIN_EMAIL {} is not verified, admin not granted", normalized_email),
+        None => tracing::warn!("BOOTSTRAP_ADMIN_EMAIL {} does not match any account", normalized_email),
+    }
+    Ok(())
+}
+
+async fn ensure_credential_exists(credential_id: i32, pool: &PgPool) -> AppResult<()> {
+    get_stored_credentials_by_id_repository(credential_id, pool)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| AppError::not_found("Credentials"))
+}
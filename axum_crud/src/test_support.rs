@@ -0,0 +1,125 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use axum::body::{to_bytes, Body, Bytes};
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+use axum::Router;
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+use crate::auth::config::AuthConfig;
+use crate::crud::error_traits::AppResult;
+use crate::grouped_routes::main_route::{main_route, AppState};
+use crate::mailer::{Email, Mailer};
+
+// Config structs read their settings from the environment, as the server does at startup
+pub fn load_env() {
+    dotenvy::dotenv().ok();
+}
+
+// A bare account with an unverified email and a throwaway password
+pub async fn create_account(pool: &PgPool, email: &str) -> i32 {
+    sqlx::query_scalar!(
+        "INSERT INTO credentials (email, password) VALUES ($1, 'not-a-real-hash') RETURNING id",
+        email
+    )
+    .fetch_one(pool)
+    .await
+    .expect("Failed to create test account")
+}
+
+// Keeps every message instead of delivering it
+#[derive(Default)]
+pub struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl RecordingMailer {
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn sent_to(&self, to: &str) -> Vec<Email> {
+        self.sent().into_iter().filter(|email| email.to == to).collect()
+    }
+
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl Mailer for RecordingMailer {
+    async fn send(&self, email: Email) -> AppResult<()> {
+        self.sent.lock().unwrap().push(email);
+        Ok(())
+    }
+}
+
+// The server's state over the test database, with mail recorded and `configure` applied to the auth settings
+pub fn test_state(pool: PgPool, mailer: Arc<RecordingMailer>, configure: impl FnOnce(&mut AuthConfig)) -> AppState {
+    load_env();
+    let mut state = AppState::from_env(pool);
+    let mut auth_config = AuthConfig::from_env();
+    configure(&mut auth_config);
+    state.auth_config = Arc::new(auth_config);
+    state.mailer = mailer;
+    state
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("Response body is not JSON")
+    }
+
+    // `name=value` of the first cookie set, ready for a Cookie header
+    pub fn cookie(&self) -> String {
+        let set_cookie = self.headers.get(header::SET_COOKIE).expect("No cookie was set");
+        set_cookie.to_str().unwrap().split(';').next().unwrap().to_string()
+    }
+}
+
+pub fn json_request(method: Method, uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+pub async fn send_json(state: &AppState, method: Method, uri: &str, body: serde_json::Value) -> TestResponse {
+    send(state, json_request(method, uri, body)).await
+}
+
+pub async fn send(state: &AppState, request: Request<Body>) -> TestResponse {
+    let response = app(state).oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    TestResponse {
+        status: parts.status,
+        headers: parts.headers,
+        body: to_bytes(body, usize::MAX).await.unwrap(),
+    }
+}
+
+fn app(state: &AppState) -> Router {
+    main_route(state.clone())
+}
+
+// Background deliveries land shortly after the response
+pub async fn wait_for_mail(mailer: &RecordingMailer, to: &str) -> Vec<Email> {
+    for _ in 0..100 {
+        let sent = mailer.sent_to(to);
+        if !sent.is_empty() {
+            return sent;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("No email was sent to {to}");
+}
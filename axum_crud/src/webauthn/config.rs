@@ -0,0 +1,17 @@
+use webauthn_rs::prelude::Url;
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+// WEBAUTHN_RP_ID must be the registrable domain of WEBAUTHN_RP_ORIGIN (the frontend, not this API)
+pub fn webauthn_from_env() -> Webauthn {
+    let rp_id = std::env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string());
+    let rp_origin = std::env::var("WEBAUTHN_RP_ORIGIN").unwrap_or_else(|_| "http://localhost:5173".to_string());
+    let rp_name = std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "axum_crud".to_string());
+
+    let rp_origin = Url::parse(&rp_origin).expect("WEBAUTHN_RP_ORIGIN must be a valid URL");
+
+    WebauthnBuilder::new(&rp_id, &rp_origin)
+        .expect("WEBAUTHN_RP_ID is not valid for WEBAUTHN_RP_ORIGIN")
+        .rp_name(&rp_name)
+        .build()
+        .expect("Failed to build WebAuthn relying party")
+}
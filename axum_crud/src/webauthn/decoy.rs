@@ -0,0 +1,84 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use webauthn_rs::prelude::RequestChallengeResponse;
+use webauthn_rs::Webauthn;
+use webauthn_rs_proto::{AllowCredentials, AuthenticatorTransport};
+
+use crate::crud::error_traits::{AppError, AppResult};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Credential id lengths seen from common authenticators (platform passkeys, password managers, security keys)
+const DECOY_CREDENTIAL_ID_BYTES: [usize; 3] = [16, 20, 32];
+
+// Transports browsers report at registration; None for those that report nothing
+const DECOY_TRANSPORTS: [Option<&[AuthenticatorTransport]>; 5] = [
+    None,
+    Some(&[AuthenticatorTransport::Internal]),
+    Some(&[AuthenticatorTransport::Hybrid, AuthenticatorTransport::Internal]),
+    Some(&[AuthenticatorTransport::Nfc, AuthenticatorTransport::Usb]),
+    Some(&[AuthenticatorTransport::Usb]),
+];
+
+// Stands in for the passkeys of emails that have none, or no account at all, so /auth/webauthn/login/start
+// answers every email the same way. Everything about the decoy entries (how many, their ids and transports)
+// is drawn from an HMAC of the email: asking twice returns the same list, as it would for a real account,
+// but it can't be linked back to the email without the secret.
+pub struct PasskeyDecoys {
+    secret: Vec<u8>,
+}
+
+impl PasskeyDecoys {
+    pub fn from_env() -> Self {
+        let secret = std::env::var("WEBAUTHN_DECOY_SECRET").expect("WEBAUTHN_DECOY_SECRET must be set");
+        Self::new(secret.into_bytes())
+    }
+
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self { secret }
+    }
+
+    // `email` must already be normalized; `index` 0 seeds the list itself, 1.. each entry
+    fn seed(&self, email: &str, index: u8) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(email.as_bytes());
+        mac.update(&[index]);
+        mac.finalize().into_bytes().into()
+    }
+
+    // Most accounts have one passkey, some two or three
+    fn credential_count(&self, email: &str) -> u8 {
+        match self.seed(email, 0)[0] {
+            0..=179 => 1,
+            180..=234 => 2,
+            _ => 3,
+        }
+    }
+
+    fn allow_credentials(&self, email: &str) -> Vec<AllowCredentials> {
+        (1..=self.credential_count(email))
+            .map(|index| {
+                let seed = self.seed(email, index);
+                let id_len = DECOY_CREDENTIAL_ID_BYTES[usize::from(seed[0]) % DECOY_CREDENTIAL_ID_BYTES.len()];
+                let transports = DECOY_TRANSPORTS[usize::from(seed[1]) % DECOY_TRANSPORTS.len()];
+                AllowCredentials {
+                    type_: "public-key".to_string(),
+                    id: seed[..id_len].to_vec().into(),
+                    transports: transports.map(<[_]>::to_vec),
+                }
+            })
+            .collect()
+    }
+
+    // The same ceremony a real account gets, pointed at the decoy entries. No state is kept, since no
+    // authenticator holds a key for those ids and the assertion can never verify.
+    pub fn options(&self, webauthn: &Webauthn, email: &str) -> AppResult<RequestChallengeResponse> {
+        let (mut options, _) = webauthn
+            .start_passkey_authentication(&[])
+            .map_err(|err| AppError::internal(format!("Failed to start passkey authentication: {err}")))?;
+
+        options.public_key.allow_credentials = self.allow_credentials(email);
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,26 @@
+
+use serde::{Deserialize};
+use uuid::Uuid;
+use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential};
+
+#[derive(Deserialize,Debug)]
+pub struct FinishRegistrationRequest {
+    pub challenge_id: Uuid,
+    pub name: Option<String>,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+// Identify the account by email (passwordless) or by the mfa_token from the password step (second factor)
+#[derive(Deserialize,Debug)]
+pub struct StartAuthenticationRequest {
+    pub email: Option<String>,
+    pub mfa_token: Option<String>,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct FinishAuthenticationRequest {
+    pub challenge_id: Uuid,
+    pub credential: PublicKeyCredential,
+    #[serde(default)]
+    pub session: bool,
+}
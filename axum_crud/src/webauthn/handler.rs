@@ -0,0 +1,73 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+    response::{IntoResponse, Response},
+    http::StatusCode,
+};
+use axum_extra::extract::cookie::CookieJar;
+use crate::auth::extractor::Authenticated;
+use crate::auth::handler::completed_login_response;
+use crate::crud::error_traits::{AppResult};
+use crate::grouped_routes::main_route::AppState;
+use crate::webauthn::dto::{FinishRegistrationRequest,StartAuthenticationRequest,FinishAuthenticationRequest};
+use crate::webauthn::services::{
+    start_registration_service,finish_registration_service,list_passkeys_service,delete_passkey_service,
+    start_authentication_service,finish_authentication_service,
+};
+
+#[axum::debug_handler]
+pub async fn start_registration_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    let response = start_registration_service(&auth, &state.db, &state.webauthn).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn finish_registration_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Json(body): Json<FinishRegistrationRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = finish_registration_service(&auth, body, &state.db, &state.webauthn).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn list_passkeys_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+) -> AppResult<impl IntoResponse> {
+    let response = list_passkeys_service(&auth, &state.db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn delete_passkey_handler(
+    State(state): State<AppState>,
+    auth: Authenticated,
+    Path(id): Path<i32>,
+) -> AppResult<impl IntoResponse> {
+    delete_passkey_service(&auth, id, &state.db).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+pub async fn start_authentication_handler(
+    State(state): State<AppState>,
+    Json(body): Json<StartAuthenticationRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = start_authentication_service(body, &state.db, &state.jwt, &state.webauthn, &state.passkey_decoys).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[axum::debug_handler]
+pub async fn finish_authentication_handler(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(body): Json<FinishAuthenticationRequest>,
+) -> AppResult<Response> {
+    let verified = finish_authentication_service(&body, &state.db, &state.webauthn, &state.auth_config).await?;
+    completed_login_response(&state, jar, verified.credential_id, verified.email, body.session).await
+}
@@ -0,0 +1,33 @@
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize};
+use uuid::Uuid;
+use webauthn_rs::prelude::{CreationChallengeResponse, Passkey, RequestChallengeResponse};
+
+
+// `options` is passed as-is to navigator.credentials.create()
+#[derive(Serialize)]
+pub struct StartRegistrationResponse {
+    pub challenge_id: Uuid,
+    pub options: CreationChallengeResponse,
+}
+
+// `options` is passed as-is to navigator.credentials.get()
+#[derive(Serialize)]
+pub struct StartAuthenticationResponse {
+    pub challenge_id: Uuid,
+    pub options: RequestChallengeResponse,
+}
+
+#[derive(Serialize)]
+pub struct PasskeyResponse {
+    pub id: i32,
+    pub name: Option<String>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+pub struct StoredPasskey {
+    pub id: i32,
+    pub passkey: Passkey,
+}
@@ -0,0 +1,235 @@
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+use webauthn_rs::prelude::Passkey;
+
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::webauthn::model::{PasskeyResponse, StoredPasskey};
+
+
+fn passkey_from_json(value: serde_json::Value) -> AppResult<Passkey> {
+    serde_json::from_value(value)
+        .map_err(|err| AppError::internal(format!("Stored passkey is unreadable: {err}")))
+}
+
+pub async fn get_webauthn_user_id_repository(
+    pool: &PgPool,
+    credential_id: i32,
+) -> AppResult<Uuid> {
+    let user_id = sqlx::query_scalar!(
+        "SELECT webauthn_user_id FROM credentials WHERE id = $1",
+        credential_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user_id)
+}
+
+pub async fn list_passkeys_repository(
+    pool: &PgPool,
+    credential_id: i32,
+) -> AppResult<Vec<StoredPasskey>> {
+    let records = sqlx::query!(
+        "SELECT id, passkey FROM webauthn_credentials WHERE credential_id = $1 ORDER BY id",
+        credential_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    records
+        .into_iter()
+        .map(|r| Ok(StoredPasskey {
+            id: r.id,
+            passkey: passkey_from_json(r.passkey)?,
+        }))
+        .collect()
+}
+
+pub async fn list_passkey_summaries_repository(
+    pool: &PgPool,
+    credential_id: i32,
+) -> AppResult<Vec<PasskeyResponse>> {
+    let records = sqlx::query!(
+        r#"
+        SELECT id, name, last_used_at, created_at
+        FROM webauthn_credentials
+        WHERE credential_id = $1
+        ORDER BY id
+        "#,
+        credential_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| PasskeyResponse {
+            id: r.id,
+            name: r.name,
+            last_used_at: r.last_used_at,
+            created_at: r.created_at,
+        })
+        .collect())
+}
+
+pub async fn has_passkeys_repository(
+    executor: impl PgExecutor<'_>,
+    credential_id: i32,
+) -> AppResult<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM webauthn_credentials WHERE credential_id = $1) AS "exists!""#,
+        credential_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(exists)
+}
+
+pub async fn insert_passkey_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    passkey: &Passkey,
+    name: Option<&str>,
+) -> AppResult<i32> {
+    let passkey_id: &[u8] = passkey.cred_id().as_ref();
+    let passkey_json = serde_json::to_value(passkey)
+        .map_err(|err| AppError::internal(format!("Failed to serialize passkey: {err}")))?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO webauthn_credentials (credential_id, passkey_id, passkey, name)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        credential_id,
+        passkey_id,
+        passkey_json,
+        name
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| match &err {
+        sqlx::Error::Database(db_err) if db_err.constraint() == Some("webauthn_credentials_passkey_id_key") => {
+            AppError::conflict("This authenticator is already registered")
+        }
+        _ => err.into(),
+    })?;
+
+    Ok(id)
+}
+
+pub async fn find_passkey_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    passkey_id: &[u8],
+) -> AppResult<Option<StoredPasskey>> {
+    let record = sqlx::query!(
+        "SELECT id, passkey FROM webauthn_credentials WHERE credential_id = $1 AND passkey_id = $2",
+        credential_id,
+        passkey_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    record
+        .map(|r| Ok(StoredPasskey {
+            id: r.id,
+            passkey: passkey_from_json(r.passkey)?,
+        }))
+        .transpose()
+}
+
+// Only stores the new counter if it moved forward (or the authenticator doesn't count at all);
+// false means a concurrent or cloned authenticator already presented this counter value
+pub async fn record_passkey_use_repository(
+    pool: &PgPool,
+    id: i32,
+    passkey: &Passkey,
+    sign_count: i64,
+) -> AppResult<bool> {
+    let passkey_json = serde_json::to_value(passkey)
+        .map_err(|err| AppError::internal(format!("Failed to serialize passkey: {err}")))?;
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE webauthn_credentials
+        SET passkey = $2, sign_count = $3, last_used_at = NOW()
+        WHERE id = $1 AND ((sign_count = 0 AND $3::BIGINT = 0) OR sign_count < $3::BIGINT)
+        "#,
+        id,
+        passkey_json,
+        sign_count
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_passkey_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    id: i32,
+) -> AppResult<bool> {
+    let result = sqlx::query!(
+        "DELETE FROM webauthn_credentials WHERE id = $1 AND credential_id = $2",
+        id,
+        credential_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn insert_challenge_repository(
+    pool: &PgPool,
+    credential_id: i32,
+    kind: &str,
+    state: serde_json::Value,
+    expires_at: DateTime<Utc>,
+) -> AppResult<Uuid> {
+    let id = Uuid::new_v4();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO webauthn_challenges (id, credential_id, kind, state, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        id,
+        credential_id,
+        kind,
+        state,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(id)
+}
+
+// Deleting on read makes every challenge single-use, even if the ceremony then fails
+pub async fn take_challenge_repository(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+    kind: &str,
+) -> AppResult<Option<(i32, serde_json::Value)>> {
+    let record = sqlx::query!(
+        r#"
+        DELETE FROM webauthn_challenges
+        WHERE id = $1 AND kind = $2
+        RETURNING credential_id, state, expires_at
+        "#,
+        id,
+        kind
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record
+        .filter(|r| r.expires_at > Utc::now())
+        .map(|r| (r.credential_id, r.state)))
+}
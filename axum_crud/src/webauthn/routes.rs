@@ -0,0 +1,19 @@
+use axum::{
+    routing::{get, post, delete},
+    Router
+};
+use crate::webauthn::handler::{
+    start_registration_handler,finish_registration_handler,list_passkeys_handler,delete_passkey_handler,
+    start_authentication_handler,finish_authentication_handler,
+};
+use crate::grouped_routes::main_route::AppState;
+
+pub fn webauthn_routes() -> Router<AppState> {
+    Router::new()
+      .route("/register/start", post(start_registration_handler))
+      .route("/register/finish", post(finish_registration_handler))
+      .route("/login/start", post(start_authentication_handler))
+      .route("/login/finish", post(finish_authentication_handler))
+      .route("/credentials", get(list_passkeys_handler))
+      .route("/credentials/{id}", delete(delete_passkey_handler))
+}
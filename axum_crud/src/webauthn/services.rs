@@ -0,0 +1,540 @@
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use sqlx::PgPool;
+use webauthn_rs::prelude::{CredentialID, PasskeyAuthentication, PasskeyRegistration};
+use webauthn_rs::Webauthn;
+
+use crate::auth::config::AuthConfig;
+use crate::auth::extractor::Authenticated;
+use crate::auth::jwt::JwtKeys;
+use crate::crud::error_traits::{AppError, AppResult};
+use crate::crud::repository::{get_stored_credentials_by_email_repository, get_stored_credentials_by_id_repository};
+use crate::crud::services::{ensure_login_verified, validate_email};
+use crate::mfa::model::VerifiedMfaLogin;
+use crate::mfa::repository::consume_pending_login_repository;
+use crate::mfa::services::decode_mfa_token;
+use crate::webauthn::decoy::PasskeyDecoys;
+use crate::webauthn::dto::{FinishAuthenticationRequest, FinishRegistrationRequest, StartAuthenticationRequest};
+use crate::webauthn::model::{PasskeyResponse, StartAuthenticationResponse, StartRegistrationResponse};
+use crate::webauthn::repository::{
+    delete_passkey_repository, find_passkey_repository, get_webauthn_user_id_repository,
+    insert_challenge_repository, insert_passkey_repository, list_passkey_summaries_repository,
+    list_passkeys_repository, record_passkey_use_repository, take_challenge_repository,
+};
+
+const CHALLENGE_TTL_SECONDS: i64 = 5 * 60;
+const REGISTRATION: &str = "registration";
+const AUTHENTICATION: &str = "authentication";
+
+// Stored for a sign-in ceremony; when the passkey is the second factor the pending password-step login
+// rides along, so finishing spends it exactly like /auth/mfa/verify would
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AuthenticationChallenge {
+    passkey: PasskeyAuthentication,
+    mfa_pending_id: Option<Uuid>,
+}
+
+fn serialize_state<T: serde::Serialize>(state: &T) -> AppResult<serde_json::Value> {
+    serde_json::to_value(state)
+        .map_err(|err| AppError::internal(format!("Failed to serialize WebAuthn state: {err}")))
+}
+
+fn deserialize_state<T: serde::de::DeserializeOwned>(state: serde_json::Value) -> AppResult<T> {
+    serde_json::from_value(state)
+        .map_err(|err| AppError::internal(format!("Stored WebAuthn state is unreadable: {err}")))
+}
+
+pub async fn start_registration_service(
+    auth: &Authenticated,
+    pool: &PgPool,
+    webauthn: &Webauthn,
+) -> AppResult<StartRegistrationResponse> {
+    let user_id = get_webauthn_user_id_repository(pool, auth.credential_id()).await?;
+
+    // Stops the browser from registering the same authenticator twice
+    let existing: Vec<CredentialID> = list_passkeys_repository(pool, auth.credential_id())
+        .await?
+        .into_iter()
+        .map(|stored| stored.passkey.cred_id().clone())
+        .collect();
+
+    let (options, state) = webauthn
+        .start_passkey_registration(user_id, auth.email(), auth.email(), Some(existing))
+        .map_err(|err| AppError::internal(format!("Failed to start passkey registration: {err}")))?;
+
+    let challenge_id = insert_challenge_repository(
+        pool,
+        auth.credential_id(),
+        REGISTRATION,
+        serialize_state(&state)?,
+        Utc::now() + Duration::seconds(CHALLENGE_TTL_SECONDS),
+    )
+    .await?;
+
+    Ok(StartRegistrationResponse { challenge_id, options })
+}
+
+pub async fn finish_registration_service(
+    auth: &Authenticated,
+    input: FinishRegistrationRequest,
+    pool: &PgPool,
+    webauthn: &Webauthn,
+) -> AppResult<PasskeyResponse> {
+    let (owner_id, state) = take_challenge_repository(pool, input.challenge_id, REGISTRATION)
+        .await?
+        .filter(|(owner_id, _)| *owner_id == auth.credential_id())
+        .ok_or_else(|| AppError::validation("Registration challenge is invalid or has expired"))?;
+
+    let state: PasskeyRegistration = deserialize_state(state)?;
+    let passkey = webauthn
+        .finish_passkey_registration(&input.credential, &state)
+        .map_err(|err| AppError::validation(format!("Passkey registration failed: {err}")))?;
+
+    let name = input.name.as_deref().map(str::trim).filter(|name| !name.is_empty());
+    let id = insert_passkey_repository(pool, owner_id, &passkey, name).await?;
+
+    Ok(PasskeyResponse {
+        id,
+        name: name.map(str::to_string),
+        last_used_at: None,
+        created_at: Some(Utc::now()),
+    })
+}
+
+pub async fn list_passkeys_service(
+    auth: &Authenticated,
+    pool: &PgPool,
+) -> AppResult<Vec<PasskeyResponse>> {
+    list_passkey_summaries_repository(pool, auth.credential_id()).await
+}
+
+pub async fn delete_passkey_service(
+    auth: &Authenticated,
+    id: i32,
+    pool: &PgPool,
+) -> AppResult<()> {
+    if !delete_passkey_repository(pool, auth.credential_id(), id).await? {
+        return Err(AppError::not_found("Passkey"));
+    }
+    Ok(())
+}
+
+// By email, an unknown account or one without passkeys gets decoy options and a challenge id that
+// was never stored, so the response doesn't tell which emails can sign in with a passkey
+pub async fn start_authentication_service(
+    input: StartAuthenticationRequest,
+    pool: &PgPool,
+    jwt: &JwtKeys,
+    webauthn: &Webauthn,
+    decoys: &PasskeyDecoys,
+) -> AppResult<StartAuthenticationResponse> {
+    let (credential_id, decoy_email, mfa_pending_id) = match (input.mfa_token, input.email) {
+        (Some(mfa_token), _) => {
+            let pending = decode_mfa_token(&mfa_token, pool, jwt).await?;
+            (Some(pending.credential_id), None, Some(pending.id))
+        }
+        (None, Some(email)) => {
+            validate_email(&email)?;
+            let normalized_email = email.to_lowercase().trim().to_string();
+            let credentials = get_stored_credentials_by_email_repository(&normalized_email, pool).await?;
+            (credentials.map(|credentials| credentials.id), Some(normalized_email), None)
+        }
+        (None, None) => return Err(AppError::validation("Either email or mfa_token is required")),
+    };
+
+    let passkeys: Vec<_> = match credential_id {
+        Some(credential_id) => list_passkeys_repository(pool, credential_id)
+            .await?
+            .into_iter()
+            .map(|stored| stored.passkey)
+            .collect(),
+        None => Vec::new(),
+    };
+    let credential_id = match (credential_id, decoy_email) {
+        (Some(credential_id), _) if !passkeys.is_empty() => credential_id,
+        (_, Some(email)) => {
+            return Ok(StartAuthenticationResponse {
+                challenge_id: Uuid::new_v4(),
+                options: decoys.options(webauthn, &email)?,
+            });
+        }
+        _ => return Err(AppError::authentication("Passkey sign-in is not available for this account")),
+    };
+
+    let (options, passkey) = webauthn
+        .start_passkey_authentication(&passkeys)
+        .map_err(|err| AppError::internal(format!("Failed to start passkey authentication: {err}")))?;
+
+    let challenge_id = insert_challenge_repository(
+        pool,
+        credential_id,
+        AUTHENTICATION,
+        serialize_state(&AuthenticationChallenge { passkey, mfa_pending_id })?,
+        Utc::now() + Duration::seconds(CHALLENGE_TTL_SECONDS),
+    )
+    .await?;
+
+    Ok(StartAuthenticationResponse { challenge_id, options })
+}
+
+// A verified assertion is a complete login on its own: the passkey proves possession and user verification.
+// As a second factor, the mfa_token is spent together with the challenge, so it can't also finish via TOTP.
+pub async fn finish_authentication_service(
+    input: &FinishAuthenticationRequest,
+    pool: &PgPool,
+    webauthn: &Webauthn,
+    config: &AuthConfig,
+) -> AppResult<VerifiedMfaLogin> {
+    let failed = || AppError::authentication("Passkey verification failed");
+
+    // Same error as a bad assertion, so a decoy challenge id can't be told apart from a real one
+    let mut tx = pool.begin().await?;
+    let (credential_id, state) = take_challenge_repository(&mut *tx, input.challenge_id, AUTHENTICATION)
+        .await?
+        .ok_or_else(failed)?;
+    let challenge: AuthenticationChallenge = deserialize_state(state)?;
+    let pending_spent = match challenge.mfa_pending_id {
+        Some(id) => !consume_pending_login_repository(&mut *tx, id).await?,
+        None => false,
+    };
+    tx.commit().await?;
+    if pending_spent {
+        return Err(failed());
+    }
+
+    let result = webauthn
+        .finish_passkey_authentication(&input.credential, &challenge.passkey)
+        .map_err(|err| {
+            tracing::warn!(credential_id, "Passkey assertion rejected: {}", err);
+            failed()
+        })?;
+
+    let mut stored = find_passkey_repository(pool, credential_id, result.cred_id().as_ref())
+        .await?
+        .ok_or_else(failed)?;
+    stored.passkey.update_credential(&result);
+
+    // A counter that didn't move forward means the key may have been cloned
+    if !record_passkey_use_repository(pool, stored.id, &stored.passkey, i64::from(result.counter())).await? {
+        tracing::warn!(credential_id, passkey = stored.id, "Passkey sign counter regressed, possible cloned authenticator");
+        return Err(failed());
+    }
+
+    let credentials = get_stored_credentials_by_id_repository(credential_id, pool)
+        .await?
+        .ok_or_else(failed)?;
+    ensure_login_verified(&credentials, config)?;
+
+    Ok(VerifiedMfaLogin {
+        credential_id,
+        email: credentials.email,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::Utc;
+    use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+    use webauthn_authenticator_rs::WebauthnAuthenticator;
+    use webauthn_rs::prelude::Url;
+    use webauthn_rs::WebauthnBuilder;
+
+    use crate::auth::extractor::AuthUser;
+    use crate::mfa::services::mfa_challenge_service;
+    use crate::mfa::totp::TotpConfig;
+    use crate::test_support::{create_account, load_env};
+
+    const ORIGIN: &str = "http://localhost:5173";
+
+    fn webauthn() -> Webauthn {
+        WebauthnBuilder::new("localhost", &Url::parse(ORIGIN).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn decoys() -> PasskeyDecoys {
+        PasskeyDecoys::new(b"test-decoy-secret".to_vec())
+    }
+
+    fn allowing_unverified(allow_unverified_login: bool) -> AuthConfig {
+        AuthConfig {
+            allow_unverified_login,
+            ..AuthConfig::from_env()
+        }
+    }
+
+    fn bearer(credential_id: i32, email: &str) -> Authenticated {
+        Authenticated::Bearer(AuthUser {
+            id: credential_id,
+            email: email.to_string(),
+            jti: Uuid::new_v4(),
+            expires_at: Utc::now() + Duration::minutes(15),
+        })
+    }
+
+    fn by_email(email: &str) -> StartAuthenticationRequest {
+        StartAuthenticationRequest {
+            email: Some(email.to_string()),
+            mfa_token: None,
+        }
+    }
+
+    async fn register_passkey(
+        authenticator: &mut WebauthnAuthenticator<SoftPasskey>,
+        credential_id: i32,
+        email: &str,
+        pool: &PgPool,
+        webauthn: &Webauthn,
+    ) {
+        let auth = bearer(credential_id, email);
+        let started = start_registration_service(&auth, pool, webauthn).await.unwrap();
+        let credential = authenticator
+            .do_registration(Url::parse(ORIGIN).unwrap(), started.options)
+            .unwrap();
+
+        let input = FinishRegistrationRequest {
+            challenge_id: started.challenge_id,
+            name: Some("Test key".to_string()),
+            credential,
+        };
+        finish_registration_service(&auth, input, pool, webauthn).await.unwrap();
+    }
+
+    async fn sign_in(
+        authenticator: &mut WebauthnAuthenticator<SoftPasskey>,
+        email: &str,
+        pool: &PgPool,
+        webauthn: &Webauthn,
+    ) -> AppResult<VerifiedMfaLogin> {
+        let started = start_authentication_service(by_email(email), pool, &JwtKeys::from_env(), webauthn, &decoys())
+            .await
+            .unwrap();
+        let credential = authenticator
+            .do_authentication(Url::parse(ORIGIN).unwrap(), started.options)
+            .unwrap();
+
+        let input = FinishAuthenticationRequest {
+            challenge_id: started.challenge_id,
+            credential,
+            session: false,
+        };
+        finish_authentication_service(&input, pool, webauthn, &allowing_unverified(true)).await
+    }
+
+    async fn stored_sign_count(pool: &PgPool, credential_id: i32) -> i64 {
+        sqlx::query_scalar!("SELECT sign_count FROM webauthn_credentials WHERE credential_id = $1", credential_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    // The options with the per-ceremony challenge and the allowed credentials taken out
+    fn options_shape(response: &StartAuthenticationResponse) -> serde_json::Value {
+        let mut options = serde_json::to_value(&response.options).unwrap();
+        options["publicKey"]["challenge"] = serde_json::Value::Null;
+        options["publicKey"]["allowCredentials"] = serde_json::Value::Null;
+        options
+    }
+
+    // Each allowed credential with its id reduced to its length
+    fn allowed_shape(response: &StartAuthenticationResponse) -> Vec<serde_json::Value> {
+        let options = serde_json::to_value(&response.options).unwrap();
+        options["publicKey"]["allowCredentials"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|allowed| {
+                let mut allowed = allowed.clone();
+                let id = URL_SAFE_NO_PAD.decode(allowed["id"].as_str().unwrap()).unwrap();
+                allowed["id"] = id.len().into();
+                allowed
+            })
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn registers_a_passkey_and_signs_in_with_it(pool: PgPool) {
+        load_env();
+        let webauthn = webauthn();
+        let id = create_account(&pool, "passkey@example.com").await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+        register_passkey(&mut authenticator, id, "passkey@example.com", &pool, &webauthn).await;
+        let passkeys = list_passkeys_service(&bearer(id, "passkey@example.com"), &pool).await.unwrap();
+        assert_eq!(passkeys.len(), 1);
+        assert_eq!(passkeys[0].name.as_deref(), Some("Test key"));
+
+        let verified = sign_in(&mut authenticator, "passkey@example.com", &pool, &webauthn).await.unwrap();
+        assert_eq!(verified.credential_id, id);
+        assert_eq!(verified.email, "passkey@example.com");
+        assert_eq!(stored_sign_count(&pool, id).await, 1);
+
+        sign_in(&mut authenticator, "passkey@example.com", &pool, &webauthn).await.unwrap();
+        assert_eq!(stored_sign_count(&pool, id).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn rejects_an_assertion_whose_sign_counter_went_backwards(pool: PgPool) {
+        load_env();
+        let webauthn = webauthn();
+        let id = create_account(&pool, "clone@example.com").await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+        register_passkey(&mut authenticator, id, "clone@example.com", &pool, &webauthn).await;
+        sign_in(&mut authenticator, "clone@example.com", &pool, &webauthn).await.unwrap();
+
+        // Another copy of the key has already signed with a higher counter
+        sqlx::query!("UPDATE webauthn_credentials SET sign_count = 10 WHERE credential_id = $1", id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let err = sign_in(&mut authenticator, "clone@example.com", &pool, &webauthn).await.unwrap_err();
+        assert!(matches!(err, AppError::Authentication { .. }));
+        assert_eq!(stored_sign_count(&pool, id).await, 10);
+    }
+
+    #[sqlx::test]
+    async fn answers_emails_without_passkeys_like_a_real_account(pool: PgPool) {
+        load_env();
+        let webauthn = webauthn();
+        let jwt = JwtKeys::from_env();
+        let id = create_account(&pool, "real@example.com").await;
+        create_account(&pool, "nopasskey@example.com").await;
+        for _ in 0..2 {
+            let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+            register_passkey(&mut authenticator, id, "real@example.com", &pool, &webauthn).await;
+        }
+        let start = |email: String| {
+            let (pool, jwt, webauthn) = (&pool, &jwt, &webauthn);
+            async move { start_authentication_service(by_email(&email), pool, jwt, webauthn, &decoys()).await.unwrap() }
+        };
+
+        let real = start("real@example.com".to_string()).await;
+        let unknown = start("unknown@example.com".to_string()).await;
+        let no_passkey = start("nopasskey@example.com".to_string()).await;
+        assert_eq!(options_shape(&unknown), options_shape(&real));
+        assert_eq!(options_shape(&no_passkey), options_shape(&real));
+
+        // Asking again for the same email offers the same credentials, as it would for a real account
+        let again = start("Unknown@example.com ".to_string()).await;
+        assert_eq!(
+            serde_json::to_value(&again.options.public_key.allow_credentials).unwrap(),
+            serde_json::to_value(&unknown.options.public_key.allow_credentials).unwrap()
+        );
+        assert_ne!(
+            serde_json::to_value(&no_passkey.options.public_key.allow_credentials).unwrap(),
+            serde_json::to_value(&unknown.options.public_key.allow_credentials).unwrap()
+        );
+
+        // Across many emails the decoys vary in count, id length and transports like real accounts do,
+        // and some look exactly like this one (two 32-byte ids, no transports reported)
+        let mut decoys = Vec::new();
+        for n in 0..200 {
+            decoys.push(allowed_shape(&start(format!("unknown{n}@example.com")).await));
+        }
+        let real_shape = allowed_shape(&real);
+        assert_eq!(real_shape.len(), 2);
+        assert!(decoys.contains(&real_shape));
+        for count in 1..=3 {
+            assert!(decoys.iter().any(|allowed| allowed.len() == count), "no decoy with {count} credentials");
+        }
+        let entries: Vec<_> = decoys.iter().flatten().collect();
+        assert!(entries.iter().any(|entry| entry.get("transports").is_some()));
+        assert!(entries.iter().all(|entry| entry["type"] == "public-key" && [16, 20, 32].contains(&entry["id"].as_u64().unwrap())));
+    }
+
+    #[sqlx::test]
+    async fn fails_a_decoy_challenge_like_a_bad_assertion(pool: PgPool) {
+        load_env();
+        let webauthn = webauthn();
+        let jwt = JwtKeys::from_env();
+        let real_id = create_account(&pool, "real@example.com").await;
+        let other_id = create_account(&pool, "other@example.com").await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register_passkey(&mut authenticator, real_id, "real@example.com", &pool, &webauthn).await;
+        register_passkey(&mut authenticator, other_id, "other@example.com", &pool, &webauthn).await;
+
+        // A valid assertion made for another account's ceremony, sent against each challenge id
+        let other = start_authentication_service(by_email("other@example.com"), &pool, &jwt, &webauthn, &decoys()).await.unwrap();
+        let stray = authenticator.do_authentication(Url::parse(ORIGIN).unwrap(), other.options).unwrap();
+
+        let real = start_authentication_service(by_email("real@example.com"), &pool, &jwt, &webauthn, &decoys()).await.unwrap();
+        let decoy = start_authentication_service(by_email("unknown@example.com"), &pool, &jwt, &webauthn, &decoys()).await.unwrap();
+
+        let finish = |challenge_id| FinishAuthenticationRequest {
+            challenge_id,
+            credential: stray.clone(),
+            session: false,
+        };
+        let real_err = finish_authentication_service(&finish(real.challenge_id), &pool, &webauthn, &allowing_unverified(true)).await.unwrap_err();
+        let decoy_err = finish_authentication_service(&finish(decoy.challenge_id), &pool, &webauthn, &allowing_unverified(true)).await.unwrap_err();
+        assert_eq!(real_err.to_string(), decoy_err.to_string());
+    }
+
+    #[sqlx::test]
+    async fn a_password_step_token_finishes_only_one_passkey_login(pool: PgPool) {
+        load_env();
+        let webauthn = webauthn();
+        let jwt = JwtKeys::from_env();
+        let id = create_account(&pool, "second@example.com").await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register_passkey(&mut authenticator, id, "second@example.com", &pool, &webauthn).await;
+
+        let mfa_token = mfa_challenge_service(id, "second@example.com", &pool, &jwt, &TotpConfig::from_env())
+            .await
+            .unwrap()
+            .unwrap()
+            .mfa_token;
+        let by_token = || StartAuthenticationRequest {
+            email: None,
+            mfa_token: Some(mfa_token.clone()),
+        };
+
+        // Two ceremonies started with the same token before either finishes
+        let mut finishes = Vec::new();
+        for _ in 0..2 {
+            let started = start_authentication_service(by_token(), &pool, &jwt, &webauthn, &decoys()).await.unwrap();
+            let credential = authenticator.do_authentication(Url::parse(ORIGIN).unwrap(), started.options).unwrap();
+            finishes.push(FinishAuthenticationRequest {
+                challenge_id: started.challenge_id,
+                credential,
+                session: false,
+            });
+        }
+
+        finish_authentication_service(&finishes[0], &pool, &webauthn, &allowing_unverified(true)).await.unwrap();
+        let replayed = finish_authentication_service(&finishes[1], &pool, &webauthn, &allowing_unverified(true)).await.unwrap_err();
+        assert_eq!(replayed.to_string(), "Authentication failed: Passkey verification failed");
+
+        // Nor can the token still be completed with TOTP or start another ceremony
+        assert!(decode_mfa_token(&mfa_token, &pool, &jwt).await.is_err());
+        assert!(start_authentication_service(by_token(), &pool, &jwt, &webauthn, &decoys()).await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn an_unverified_address_cannot_sign_in_by_passkey_alone(pool: PgPool) {
+        load_env();
+        let webauthn = webauthn();
+        let id = create_account(&pool, "unverified@example.com").await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register_passkey(&mut authenticator, id, "unverified@example.com", &pool, &webauthn).await;
+
+        let started = start_authentication_service(by_email("unverified@example.com"), &pool, &JwtKeys::from_env(), &webauthn, &decoys())
+            .await
+            .unwrap();
+        let credential = authenticator.do_authentication(Url::parse(ORIGIN).unwrap(), started.options).unwrap();
+        let input = FinishAuthenticationRequest {
+            challenge_id: started.challenge_id,
+            credential,
+            session: false,
+        };
+
+        let err = finish_authentication_service(&input, &pool, &webauthn, &allowing_unverified(false)).await.unwrap_err();
+        assert_eq!(err.to_string(), "Authentication failed: Email address has not been verified");
+    }
+}